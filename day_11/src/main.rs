@@ -1,8 +1,6 @@
 use intcode::*;
-use std::collections::HashMap;
 
-type Panel = (i32, i32);
-type PaintedGrid = HashMap<Panel, u8>;
+type Panel = (i64, i64);
 
 enum Facing {
     North,
@@ -43,22 +41,31 @@ struct PaintBot {
     brain: IntcodeComputer,
     facing_dir: Facing,
     location: Panel,
-    grid: PaintedGrid,
+    grid: Display2D,
+}
+
+/// Unpainted panels are black.
+fn hull_palette() -> Palette {
+    Palette::new('▓', [0, 0, 0]).with(1, '░', [255, 255, 255])
 }
 
 impl PaintBot {
-    fn new(program: &'static str) -> PaintBot {
+    fn new(program: &[i64]) -> PaintBot {
         PaintBot {
-            brain: IntcodeComputer::new(&parse_program(program)),
+            brain: IntcodeComputer::new(program),
             facing_dir: Facing::North,
             location: (0, 0),
-            grid: PaintedGrid::new(),
+            grid: Display2D::new(hull_palette()),
         }
     }
 
-    fn paint_panel(&mut self, panel: Panel, color: u8) {
-        let panel_color = self.grid.entry(panel).or_insert(0);
-        *panel_color = color;
+    /// Keeps every step of the painting as a frame of the grid.
+    fn record_frames(&mut self) {
+        self.grid.record_frames();
+    }
+
+    fn paint_panel(&mut self, panel: Panel, color: i64) {
+        self.grid.write(panel.0, panel.1, color);
     }
 
     fn rotate_and_move(&mut self, rot: Rotate) {
@@ -76,14 +83,19 @@ impl PaintBot {
             return false;
         }
 
-        let panel_color = self.grid.get(&self.location).unwrap_or(&0);
-        self.paint_panel(self.location, (*self.brain.run(vec![*panel_color as i64]).last().unwrap()) as u8);
-        self.rotate_and_move(if *(self.brain.run(vec![]).last().unwrap()) == 0 { Rotate::Left } else { Rotate::Right });
-
-        true
+        let panel_color = self.grid.get(self.location.0, self.location.1).unwrap_or(0);
+        match self.brain.outputs(vec![panel_color]).frames::<(i64, i64)>().next() {
+            Some((color, turn)) => {
+                self.paint_panel(self.location, color);
+                self.rotate_and_move(if turn == 0 { Rotate::Left } else { Rotate::Right });
+                self.grid.end_frame();
+                true
+            }
+            None => false,
+        }
     }
 
-    fn paint(&mut self, starting_panel_color: u8) -> PaintedGrid {
+    fn paint(&mut self, starting_panel_color: i64) -> Display2D {
         self.paint_panel(self.location, starting_panel_color);
         loop {
             if !self.tick() {
@@ -95,35 +107,55 @@ impl PaintBot {
     }
 }
 
+fn usage() -> ! {
+    eprintln!("Usage: day_11 [--frames <dir>] [--scale <pixels>] [--palette <value=rrggbb,...>] [program]");
+    std::process::exit(2);
+}
+
 fn main() {
+    let mut program_path = None;
+    let mut frames_dir = None;
+    let mut scale = 4;
+    let mut palette = hull_palette();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--frames" => frames_dir = Some(value()),
+            "--scale" => scale = value().parse().ok().filter(|scale| *scale > 0).unwrap_or_else(|| usage()),
+            "--palette" => {
+                palette = palette.with_colours(&value()).unwrap_or_else(|err| {
+                    eprintln!("Bad palette: {}", err);
+                    usage()
+                })
+            }
+            _ if arg.starts_with('-') => usage(),
+            _ => program_path = Some(arg),
+        }
+    }
+    let program = program_or_default(program_path.as_deref(), include_str!("../input/day_11.txt"));
+
     println!(
         "Part 1 => {}",
-        PaintBot::new(include_str!("../input/day_11.txt"))
+        PaintBot::new(&program)
             .paint(0)
-            .keys()
             .len()
     );
 
-    let painted_panels = PaintBot::new(include_str!("../input/day_11.txt")).paint(1);
-    let min_x = painted_panels.keys().map(|p| p.0).min();
-    let max_x = painted_panels.keys().map(|p| p.0).max();
-
-    let min_y = painted_panels.keys().map(|p| p.1).min();
-    let max_y = painted_panels.keys().map(|p| p.1).max();
-
+    let mut bot = PaintBot::new(&program);
+    if frames_dir.is_some() {
+        bot.record_frames();
+    }
+    let mut painted_panels = bot.paint(1);
     println!("Part 2 => \n");
-    for y in min_y.unwrap()..=max_y.unwrap() {
-        for x in min_x.unwrap()..max_x.unwrap() {
-            print!(
-                "{}",
-                if *painted_panels.get(&(x, y)).unwrap_or(&0) == 0 {
-                    '▓'
-                } else {
-                    '░'
-                }
-            );
-        }
-        println!("");
+    print!("{}", painted_panels.render());
+
+    if let Some(dir) = frames_dir {
+        painted_panels.set_palette(palette);
+        let count = save_ppm_frames(painted_panels.frame_images(scale), &dir)
+            .unwrap_or_else(|err| panic!("Could not save frames to {}: {}", dir, err));
+        println!("Saved {} frames to {}", count, dir);
     }
 }
 
@@ -131,9 +163,37 @@ fn main() {
 fn part_1_complete() {
     assert_eq!(
         2255,
-        PaintBot::new(include_str!("../input/day_11.txt"))
+        PaintBot::new(&parse_program(include_str!("../input/day_11.txt")).unwrap())
             .paint(0)
-            .keys()
             .len()
     );
 }
+
+#[test]
+fn painting_frames() {
+    let mut bot = PaintBot::new(&parse_program(include_str!("../input/day_11.txt")).unwrap());
+    bot.record_frames();
+    let painted_panels = bot.paint(1);
+
+    // One frame per step, all the size of the finished hull
+    let frames = painted_panels.frame_images(1).collect::<Vec<_>>();
+    assert_eq!(frames.len(), painted_panels.frame_count());
+    assert!(frames.iter().all(|frame| (frame.width, frame.height) == (43, 6)));
+    assert_eq!(frames.last(), Some(&painted_panels.to_image()));
+}
+
+#[test]
+fn part_2_complete() {
+    // Reads BCKFPCRA
+    assert_eq!(
+        PaintBot::new(&parse_program(include_str!("../input/day_11.txt")).unwrap()).paint(1).render(),
+        concat!(
+            "▓░░░▓▓▓░░▓▓░▓▓░▓░░░░▓░░░▓▓▓░░▓▓░░░▓▓▓░░▓▓▓▓\n",
+            "▓░▓▓░▓░▓▓░▓░▓░▓▓░▓▓▓▓░▓▓░▓░▓▓░▓░▓▓░▓░▓▓░▓▓▓\n",
+            "▓░░░▓▓░▓▓▓▓░░▓▓▓░░░▓▓░▓▓░▓░▓▓▓▓░▓▓░▓░▓▓░▓▓▓\n",
+            "▓░▓▓░▓░▓▓▓▓░▓░▓▓░▓▓▓▓░░░▓▓░▓▓▓▓░░░▓▓░░░░▓▓▓\n",
+            "▓░▓▓░▓░▓▓░▓░▓░▓▓░▓▓▓▓░▓▓▓▓░▓▓░▓░▓░▓▓░▓▓░▓▓▓\n",
+            "▓░░░▓▓▓░░▓▓░▓▓░▓░▓▓▓▓░▓▓▓▓▓░░▓▓░▓▓░▓░▓▓░▓▓▓\n",
+        )
+    );
+}
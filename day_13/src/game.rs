@@ -0,0 +1,236 @@
+use crate::screen::{Message, Screen, Tile};
+use crate::strategy::JoystickStrategy;
+use console::Term;
+use intcode::*;
+
+/// Gets a look at the game after every frame, such as to draw it.
+pub trait Observer {
+    fn observe(&mut self, game: &Game);
+}
+
+/// Draws every frame on the terminal, and waits for a key once the game is over.
+pub struct TerminalObserver {
+    terminal: Term,
+}
+
+impl TerminalObserver {
+    pub fn new() -> TerminalObserver {
+        let terminal = Term::stdout();
+        terminal.clear_screen().unwrap();
+        TerminalObserver { terminal }
+    }
+}
+
+impl Observer for TerminalObserver {
+    fn observe(&mut self, game: &Game) {
+        self.terminal.move_cursor_to(0, 0).unwrap();
+        self.terminal.write_line(&format!("Score: {}  Blocks: {}  Frame: {}", game.score(), game.blocks(), game.frames())).unwrap();
+        self.terminal.write_str(&game.screen().to_string()).unwrap();
+
+        if game.finished() {
+            self.terminal.read_key().unwrap();
+        }
+    }
+}
+
+/// Everything that decides how the rest of a game can go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct State {
+    pub ball: Option<(i64, i64)>,
+    /// How far the ball moved on the last frame.
+    pub velocity: (i64, i64),
+    pub paddle: Option<i64>,
+    /// Fingerprint of which blocks have been broken.
+    pub blocks: u64,
+}
+
+/// A well mixed hash of a position, so that XORing them together fingerprints
+/// a set of positions.
+fn position_hash(x: i64, y: i64) -> u64 {
+    let mut z = ((x as u64) << 32 ^ (y as u64)).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Instructions a frame may take before the cabinet is taken to have hung.
+const FRAME_LIMIT: u64 = 1_000_000;
+
+/// The arcade cabinet on free play. A frame is everything the cabinet draws
+/// between two moves of the joystick.
+pub struct Game {
+    computer: IntcodeComputer,
+    screen: Screen,
+    velocity: (i64, i64),
+    block_fingerprint: u64,
+    cleared_at: Option<u64>,
+    hung: bool,
+    frames: u64,
+    moves: Vec<i64>,
+}
+
+impl Game {
+    /// Inserts the quarters and runs the cabinet up to the first frame.
+    pub fn new(program: &[i64]) -> Game {
+        let mut hacked_memory = program.to_vec();
+        hacked_memory[0] = 2;
+
+        let mut game = Game {
+            computer: IntcodeComputer::new(&hacked_memory),
+            screen: Screen::new(),
+            velocity: (0, 0),
+            block_fingerprint: 0,
+            cleared_at: None,
+            hung: false,
+            frames: 0,
+            moves: vec![],
+        };
+        game.advance();
+        game.screen.end_frame();
+        game
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn score(&self) -> i64 {
+        self.screen.score()
+    }
+
+    /// `None` until the cabinet draws the ball.
+    pub fn ball(&self) -> Option<(i64, i64)> {
+        self.screen.ball()
+    }
+
+    /// `None` until the cabinet draws the paddle.
+    pub fn paddle(&self) -> Option<(i64, i64)> {
+        self.screen.paddle()
+    }
+
+    /// How far the ball is to the right of the paddle, or `None` until both
+    /// are drawn.
+    pub fn ball_offset(&self) -> Option<i64> {
+        Some(self.ball()?.0 - self.paddle()?.0)
+    }
+
+    /// Number of joystick moves made so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Every joystick move made so far, in order. A fork only has the moves
+    /// made since it was forked.
+    pub fn moves(&self) -> &[i64] {
+        &self.moves
+    }
+
+    /// Blocks left on the board.
+    pub fn blocks(&self) -> usize {
+        self.screen.count(Tile::Block)
+    }
+
+    pub fn state(&self) -> State {
+        State {
+            ball: self.ball(),
+            velocity: self.velocity,
+            paddle: self.paddle().map(|(x, _)| x),
+            blocks: self.block_fingerprint,
+        }
+    }
+
+    /// The frame on which the last block was broken, if it has been.
+    pub fn cleared_at(&self) -> Option<u64> {
+        self.cleared_at
+    }
+
+    /// Whether the program halted, or hung without asking for the joystick.
+    /// The cabinet hangs if the paddle is moved into the ball from the side.
+    pub fn finished(&self) -> bool {
+        self.computer.finished() || self.hung
+    }
+
+    /// Keeps every frame of the board from now on.
+    pub fn record_frames(&mut self) {
+        self.screen.record_frames();
+    }
+
+    /// An independent copy of the game in its current state, to try moves on.
+    /// It carries on counting frames, but leaves the moves and the screen's
+    /// history behind, so forking takes as long on the last frame as the first.
+    pub fn fork(&self) -> Game {
+        let computer = IntcodeComputer::from_snapshot(&self.computer.snapshot());
+
+        Game {
+            computer,
+            screen: self.screen.fork(),
+            velocity: self.velocity,
+            block_fingerprint: self.block_fingerprint,
+            cleared_at: self.cleared_at,
+            hung: self.hung,
+            frames: self.frames,
+            moves: vec![],
+        }
+    }
+
+    /// Moves the joystick, -1 for left, 0 for neutral and 1 for right, and runs
+    /// the cabinet until the next frame is drawn.
+    pub fn step(&mut self, joystick: i64) {
+        if self.finished() {
+            return;
+        }
+
+        self.computer.provide_input(joystick);
+        self.frames += 1;
+        self.moves.push(joystick);
+        let (ball, broken) = (self.ball(), self.screen.broken_blocks().len());
+        self.advance();
+        self.screen.end_frame();
+
+        self.velocity = match (ball, self.ball()) {
+            (Some(before), Some(after)) => (after.0 - before.0, after.1 - before.1),
+            _ => (0, 0),
+        };
+        for (_, (x, y)) in &self.screen.broken_blocks()[broken..] {
+            self.block_fingerprint ^= position_hash(*x, *y);
+        }
+        if self.blocks() == 0 && self.cleared_at.is_none() {
+            self.cleared_at = Some(self.frames());
+        }
+    }
+
+    fn advance(&mut self) {
+        let computer = &mut self.computer;
+        let hung = &mut self.hung;
+        let limit = computer.instruction_count() + FRAME_LIMIT;
+        let outputs = std::iter::from_fn(|| loop {
+            if computer.instruction_count() > limit {
+                *hung = true;
+                return None;
+            }
+            match computer.step() {
+                Some(Output::OutputVal(val)) => return Some(val),
+                Some(Output::WaitingForInput) | Some(Output::Exit) => return None,
+                None => {}
+            }
+        });
+
+        self.screen.extend(outputs.frames::<Message>());
+    }
+
+    /// Plays until the game is over, moving the joystick as `strategy` says, and
+    /// returns the final score. `observer` is shown every frame.
+    pub fn play(&mut self, strategy: &mut dyn JoystickStrategy, mut observer: Option<&mut dyn Observer>) -> i64 {
+        loop {
+            if let Some(observer) = observer.as_mut() {
+                observer.observe(self);
+            }
+            if self.finished() {
+                return self.score();
+            }
+
+            let joystick = strategy.joystick(self);
+            self.step(joystick);
+        }
+    }
+}
@@ -1,114 +1,277 @@
+mod game;
+mod screen;
+mod solver;
+mod strategy;
+
+use game::{Game, TerminalObserver};
 use intcode::*;
-use console::Term;
-
-enum Tile {
-    Empty,
-    Wall,
-    Block,
-    Paddle,
-    Ball
+use screen::{Message, Screen, Tile};
+use solver::Solver;
+use strategy::{FollowBall, JoystickStrategy, Keyboard, Predictive, Replay};
+
+fn count_blocks(program: &[i64]) -> usize {
+    let mut screen = Screen::new();
+    screen.extend(IntcodeComputer::new(program).outputs(vec!()).frames::<Message>());
+    screen.count(Tile::Block)
 }
 
-impl Tile {
-    fn from_int(i: i64) -> Tile {
-        match i {
-            0 => Tile::Empty,
-            1 => Tile::Wall,
-            2 => Tile::Block,
-            3 => Tile::Paddle,
-            4 => Tile::Ball,
-            _ => panic!("Unsupported Tile type")
+fn print_history(screen: &Screen) {
+    println!("frame    score  blocks  paddle  ball");
+    let mut scores = screen.score_history().iter().peekable();
+    let mut score = 0;
+    for (frame, counts) in screen.count_history() {
+        while let Some((_, new_score)) = scores.next_if(|(at, _)| at <= frame) {
+            score = *new_score;
         }
+        let count = |tile: Tile| counts[tile as usize];
+        println!("{:5}  {:7}  {:6}  {:6}  {:4}", frame, score, count(Tile::Block), count(Tile::Paddle), count(Tile::Ball));
     }
 }
 
-impl std::fmt::Display for Tile {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let c = match *self {
-            Tile::Empty => ' ',
-            Tile::Wall => '█',
-            Tile::Block => '▒',
-            Tile::Paddle => '▃',
-            Tile::Ball => '●'
+fn usage() -> ! {
+    eprintln!(concat!(
+        "Usage: day_13 [--strategy follow|predict|keyboard] [--replay <moves>] [--record <moves>] [--compare]\n",
+        "              [--solve <max frames> [--beam <width>]] [--history]\n",
+        "              [--frames <dir> [--scale <pixels>] [--palette <tile=rrggbb,...>]] [program]\n",
+        "With --frames the game runs without drawing on the terminal. --history lists the score\n",
+        "and tile counts after every frame that changed them."
+    ));
+    std::process::exit(2);
+}
+
+fn main() {
+    let mut program_path = None;
+    let mut strategy: Box<dyn JoystickStrategy> = Box::new(FollowBall);
+    let mut record_path = None;
+    let mut compare = false;
+    let mut history = false;
+    let mut solve_frames = None;
+    let mut beam = None;
+    let mut frames_dir = None;
+    let mut scale = 4;
+    let mut palette = screen::palette();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strategy" => {
+                strategy = match args.next().as_deref() {
+                    Some("follow") => Box::new(FollowBall),
+                    Some("predict") => Box::new(Predictive::default()),
+                    Some("keyboard") => Box::new(Keyboard::new()),
+                    _ => usage(),
+                }
+            }
+            "--replay" => {
+                let path = args.next().unwrap_or_else(|| usage());
+                strategy = Box::new(Replay::load(&path).unwrap_or_else(|err| panic!("Could not load {}: {}", path, err)));
+            }
+            "--record" => record_path = Some(args.next().unwrap_or_else(|| usage())),
+            "--compare" => compare = true,
+            "--history" => history = true,
+            "--solve" => solve_frames = Some(args.next().and_then(|frames| frames.parse().ok()).unwrap_or_else(|| usage())),
+            "--beam" => beam = Some(args.next().and_then(|width| width.parse().ok()).unwrap_or_else(|| usage())),
+            "--frames" => frames_dir = Some(args.next().unwrap_or_else(|| usage())),
+            "--scale" => scale = args.next().and_then(|scale| scale.parse().ok()).filter(|scale| *scale > 0).unwrap_or_else(|| usage()),
+            "--palette" => {
+                let spec = args.next().unwrap_or_else(|| usage());
+                palette = palette.with_colours(&spec).unwrap_or_else(|err| {
+                    eprintln!("Bad palette: {}", err);
+                    usage()
+                });
+            }
+            _ if arg.starts_with('-') => usage(),
+            _ => program_path = Some(arg),
+        }
+    }
+
+    let program = program_or_default(program_path.as_deref(), include_str!("../input/day_13.txt"));
+    println!("Part 1 => {}", count_blocks(&program));
+
+    if compare {
+        let mut strategies: Vec<Box<dyn JoystickStrategy>> = vec![Box::new(FollowBall), Box::new(Predictive::default())];
+        println!("{:<10} {:>8} {:>8} {:>8} {:>8}", "strategy", "score", "frames", "cleared", "moves");
+        for result in strategy::compare(&program, &mut strategies) {
+            let cleared = result.cleared_at.map_or("-".to_string(), |frame| frame.to_string());
+            println!("{:<10} {:>8} {:>8} {:>8} {:>8}", result.name, result.score, result.frames, cleared, result.paddle_moves);
+        }
+        return;
+    }
+
+    if let Some(max_frames) = solve_frames {
+        let solver = match beam {
+            Some(width) => Solver::new(max_frames).beam(width),
+            None => Solver::new(max_frames),
         };
+        let solution = solver.solve(&Game::new(&program));
+        println!(
+            "Best score {} after {} frames, {}{}",
+            solution.score,
+            solution.frames,
+            if solution.cleared { "board cleared" } else { "board not cleared" },
+            if solution.exhaustive { "" } else { " (the beam dropped states, so this may not be optimal)" }
+        );
+        if let Some(path) = &record_path {
+            strategy::save_moves(path, &solution.moves).unwrap_or_else(|err| panic!("Could not save {}: {}", path, err));
+        }
+        return;
+    }
+
+    let mut game = Game::new(&program);
+    if frames_dir.is_some() {
+        game.record_frames();
+        println!("Part 2 => {}", game.play(strategy.as_mut(), None));
+    } else {
+        println!("Part 2 => {}", game.play(strategy.as_mut(), Some(&mut TerminalObserver::new())));
+    }
 
-        write!(f, "{}", c)
+    if history {
+        print_history(game.screen());
+    }
+    if let Some(dir) = frames_dir {
+        let mut board = game.screen().board().clone();
+        board.set_palette(palette);
+        let count = save_ppm_frames(board.frame_images(scale), &dir)
+            .unwrap_or_else(|err| panic!("Could not save frames to {}: {}", dir, err));
+        println!("Saved {} frames to {}", count, dir);
+    }
+    if let Some(path) = record_path {
+        strategy::save_moves(&path, game.moves()).unwrap_or_else(|err| panic!("Could not save {}: {}", path, err));
     }
 }
 
-fn play_game(initial_memory: &Vec<i64>) -> i64 {
-    let mut hacked_memory = initial_memory.clone();
-    hacked_memory[0] = 2;
+#[test]
+fn part_1_complete() {
+    assert_eq!(count_blocks(&parse_program(include_str!("../input/day_13.txt")).unwrap()), 213);
+}
 
-    let mut computer = IntcodeComputer::new(&hacked_memory);
-    let mut frame_buffer = vec!();
-    let mut score = 0;
+#[test]
+fn game_starts_with_full_board() {
+    let game = Game::new(&parse_program(include_str!("../input/day_13.txt")).unwrap());
+    let blocks = game.screen().board().iter().filter(|(_, tile)| *tile == Tile::Block as i64).count();
+    assert_eq!(blocks, 213);
+    assert_eq!((game.score(), game.frames()), (0, 0));
+    assert!(game.ball().unwrap().1 < game.paddle().unwrap().1);
+}
 
-    let terminal = Term::stdout();
-    terminal.clear_screen().unwrap();
-
-    let mut ball_x = 0;
-    let mut paddle_x = 0;
-
-    loop {
-        let next_frame = computer.tick();
-        match next_frame {
-            Output::OutputVal(x) => {
-                frame_buffer.push(x);
-                if frame_buffer.len() == 3 {
-                    if frame_buffer[0] == -1 && frame_buffer[1] == 0 {
-                        score = frame_buffer[2];
-                        terminal.move_cursor_to(0, 0).unwrap();
-                        terminal.write_line(&format!("Score: {}", score)).unwrap();
-                    } else {
-                        let x = frame_buffer[0] as usize;
-                        let y = frame_buffer[1] as usize;
-                        let tile_type = Tile::from_int(frame_buffer[2]);
-
-                        match tile_type {
-                            Tile::Ball => ball_x = x,
-                            Tile::Paddle => paddle_x = x,
-                            _ => {},
-                        }
-                        
-                        terminal.move_cursor_to(x, y + 1).unwrap();
-                        terminal.write_line(&tile_type.to_string()).unwrap();
-                    }
-                    frame_buffer.clear();
-                }
-            },
-            Output::WaitingForInput => {
-                computer.provide_input(if paddle_x < ball_x {1} else if paddle_x > ball_x {-1} else {0});
-            },
-            Output::Exit => {
-                terminal.read_key().unwrap();
-                break;
-            }
-        }
+#[test]
+fn game_before_anything_is_drawn() {
+    // Multiplies and halts once the quarters are in
+    let mut game = Game::new(&[1, 0, 0, 0, 99]);
+    assert_eq!((game.ball(), game.paddle(), game.ball_offset()), (None, None, None));
+    assert_eq!(FollowBall.joystick(&game), 0);
+    assert_eq!(Predictive::default().joystick(&game), 0);
+    assert_eq!(game.state().ball, None);
+    game.step(1);
+    assert!(game.finished());
+}
+
+#[test]
+fn screen_history() {
+    let mut game = Game::new(&parse_program(include_str!("../input/day_13.txt")).unwrap());
+    game.play(&mut FollowBall, None);
+    let screen = game.screen();
+
+    let (first_frame, first_counts) = screen.count_history()[0];
+    assert_eq!((first_frame, first_counts[Tile::Block as usize]), (0, 213));
+    assert_eq!((screen.count(Tile::Ball), screen.count(Tile::Paddle)), (1, 1));
+
+    // Every block breaks on the frame its score is shown
+    let broken = screen.broken_blocks();
+    assert_eq!(broken.len(), 213);
+    assert_eq!(broken.last().map(|(frame, _)| *frame), game.cleared_at());
+    let score_frames = screen.score_history().iter().map(|(frame, _)| *frame);
+    assert!(score_frames.eq(broken.iter().map(|(frame, _)| *frame)));
+    assert_eq!(screen.score_history().last(), Some(&(5742, 11441)));
+
+    let walls = screen.walls().unwrap();
+    assert_eq!(Some(walls), screen.board().bounds());
+    let text = screen.to_string();
+    assert_eq!(text.lines().count(), walls.height());
+    assert!(text.lines().next().unwrap().chars().all(|c| c == '█'));
+}
+
+#[test]
+fn part_2_complete() {
+    let mut game = Game::new(&parse_program(include_str!("../input/day_13.txt")).unwrap());
+    assert_eq!(game.play(&mut FollowBall, None), 11441);
+    assert!(game.finished());
+    assert_eq!(game.blocks(), 0);
+}
+
+#[test]
+fn game_frames() {
+    let mut game = Game::new(&parse_program(include_str!("../input/day_13.txt")).unwrap());
+    game.record_frames();
+    for _ in 0..3 {
+        game.step(0);
     }
 
-    score
+    // The board as it started, then one frame per move
+    let frames = game.screen().board().frame_images(1).collect::<Vec<_>>();
+    assert_eq!(frames.len(), 4);
+    assert_ne!(frames[0], frames[3]);
+    assert_eq!(frames[3], game.screen().board().to_image());
+
+    // Copies to try moves on leave the recording behind
+    assert_eq!(game.fork().screen().board().frame_count(), 0);
 }
 
-fn main() {
-    let program = parse_program(include_str!("../input/day_13.txt"));
-    let num_blocks = IntcodeComputer::new(&program)
-                                        .run(vec!())
-                                        .chunks_exact(3)
-                                        .filter(|cmd| cmd[2] == 2)
-                                        .count();
-    println!("Part 1 => {}", num_blocks);
-
-    println!("Part 2 => {}", play_game(&program));
+#[test]
+fn strategies_compared() {
+    let program = parse_program(include_str!("../input/day_13.txt")).unwrap();
+    let mut strategies: Vec<Box<dyn JoystickStrategy>> = vec![Box::new(FollowBall), Box::new(Predictive::default())];
+    let results = strategy::compare(&program, &mut strategies);
+
+    assert_eq!(results.iter().map(|r| r.score).collect::<Vec<i64>>(), vec![11441, 11441]);
+    assert!(results.iter().all(|r| r.cleared_at == Some(r.frames)));
+
+    // The ball bounces the same wherever the paddle meets it, so both games
+    // take as long, but moving early saves chasing the ball
+    assert_eq!(results[0].frames, results[1].frames);
+    assert!(results[1].paddle_moves < results[0].paddle_moves / 2);
+
+    // Replaying the moves of a game plays it out the same way
+    let mut game = Game::new(&program);
+    game.play(&mut Predictive::default(), None);
+    let moves = strategy::parse_moves(&strategy::format_moves(game.moves())).unwrap();
+    assert_eq!(moves, game.moves());
+    let mut replayed = Game::new(&program);
+    assert_eq!(replayed.play(&mut Replay::new(moves), None), 11441);
+    assert_eq!(replayed.moves(), game.moves());
 }
 
 #[test]
-fn part_1_complete() {
-    let num_blocks = IntcodeComputer::new(&parse_program(include_str!("../input/day_13.txt")))
-    .run(vec!())
-    .chunks_exact(3)
-    .filter(|cmd| cmd[2] == 2)
-    .count();
-
-    assert_eq!(num_blocks, 213);
-}
\ No newline at end of file
+fn solver_replays() {
+    let program = parse_program(include_str!("../input/day_13.txt")).unwrap();
+    let replayed = |moves: &[i64]| {
+        let mut game = Game::new(&program);
+        let mut replay = Replay::new(moves.to_vec());
+        for _ in 0..moves.len() {
+            let joystick = replay.joystick(&game);
+            game.step(joystick);
+        }
+        game
+    };
+
+    let solution = Solver::new(60).solve(&Game::new(&program));
+    assert_eq!((solution.cleared, solution.exhaustive), (false, true));
+    assert!(solution.frames <= 60);
+    assert_eq!(solution.moves.len() as u64, solution.frames);
+
+    let mut following = Game::new(&program);
+    for _ in 0..60 {
+        let joystick = FollowBall.joystick(&following);
+        following.step(joystick);
+    }
+    assert!(solution.score >= following.score());
+
+    let game = replayed(&solution.moves);
+    assert_eq!(game.score(), solution.score);
+    assert!(!game.finished());
+
+    // A beam searches further, but gives up on being exact
+    let solution = Solver::new(300).beam(16).solve(&Game::new(&program));
+    assert!(!solution.exhaustive);
+    assert_eq!(replayed(&solution.moves).score(), solution.score);
+}
@@ -0,0 +1,245 @@
+use intcode::display::Bounds;
+use intcode::*;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tile {
+    Empty = 0,
+    Wall = 1,
+    Block = 2,
+    Paddle = 3,
+    Ball = 4,
+}
+
+impl Tile {
+    pub fn from_int(i: i64) -> Tile {
+        match i {
+            0 => Tile::Empty,
+            1 => Tile::Wall,
+            2 => Tile::Block,
+            3 => Tile::Paddle,
+            4 => Tile::Ball,
+            _ => panic!("Unsupported Tile type")
+        }
+    }
+
+    fn glyph(&self) -> char {
+        match *self {
+            Tile::Empty => ' ',
+            Tile::Wall => '█',
+            Tile::Block => '▒',
+            Tile::Paddle => '▃',
+            Tile::Ball => '●'
+        }
+    }
+
+    fn colour(&self) -> [u8; 3] {
+        match *self {
+            Tile::Empty => [0, 0, 0],
+            Tile::Wall => [128, 128, 128],
+            Tile::Block => [230, 120, 30],
+            Tile::Paddle => [255, 255, 255],
+            Tile::Ball => [220, 40, 40],
+        }
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.glyph())
+    }
+}
+
+/// Draws each tile value with the tile's glyph.
+pub fn palette() -> Palette {
+    (0..=4).map(Tile::from_int).fold(Palette::new(' ', [0, 0, 0]), |palette, tile| {
+        palette.with(tile as i64, tile.glyph(), tile.colour())
+    })
+}
+
+pub enum Message {
+    Draw(i64, i64, Tile),
+    Score(i64),
+}
+
+impl Decoder for Message {
+    const ARITY: usize = 3;
+
+    fn decode(frame: &[i64]) -> Message {
+        match *frame {
+            [-1, 0, score] => Message::Score(score),
+            [x, y, tile] => Message::Draw(x, y, Tile::from_int(tile)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Number of tiles of each type on the screen, indexed by `Tile as usize`.
+pub type TileCounts = [usize; 5];
+
+/// Everything the cabinet has drawn, and how it changed from frame to frame.
+/// Frames are numbered from 0, the screen as it is first drawn.
+#[derive(Clone, Debug)]
+pub struct Screen {
+    board: Display2D,
+    counts: TileCounts,
+    /// The counts at the end of every frame that changed them.
+    count_history: Vec<(u64, TileCounts)>,
+    score: i64,
+    score_history: Vec<(u64, i64)>,
+    broken: Vec<(u64, (i64, i64))>,
+    walls: Option<Bounds>,
+    ball: Option<(i64, i64)>,
+    paddle: Option<(i64, i64)>,
+    frame: u64,
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen {
+            board: Display2D::new(palette()),
+            counts: [0; 5],
+            count_history: vec![],
+            score: 0,
+            score_history: vec![],
+            broken: vec![],
+            walls: None,
+            ball: None,
+            paddle: None,
+            frame: 0,
+        }
+    }
+
+    pub fn apply(&mut self, message: Message) {
+        match message {
+            Message::Score(score) => {
+                if score != self.score {
+                    self.score = score;
+                    self.score_history.push((self.frame, score));
+                }
+            }
+            Message::Draw(x, y, tile) => self.draw(x, y, tile),
+        }
+    }
+
+    fn draw(&mut self, x: i64, y: i64, tile: Tile) {
+        let previous = self.board.get(x, y).map(Tile::from_int);
+        if let Some(previous) = previous {
+            self.counts[previous as usize] -= 1;
+        }
+        self.counts[tile as usize] += 1;
+
+        if previous == Some(Tile::Block) && tile != Tile::Block {
+            self.broken.push((self.frame, (x, y)));
+        }
+        match tile {
+            Tile::Wall => self.walls = Some(self.walls.map_or(Bounds::point(x, y), |walls| walls.including(x, y))),
+            Tile::Ball => self.ball = Some((x, y)),
+            Tile::Paddle => self.paddle = Some((x, y)),
+            _ => {}
+        }
+        self.board.write(x, y, tile as i64);
+    }
+
+    /// Moves on to the next frame.
+    pub fn end_frame(&mut self) {
+        if self.count_history.last().is_none_or(|(_, counts)| *counts != self.counts) {
+            self.count_history.push((self.frame, self.counts));
+        }
+        self.board.end_frame();
+        self.frame += 1;
+    }
+
+    /// The tiles as a `Display2D`, drawn with `palette`.
+    pub fn board(&self) -> &Display2D {
+        &self.board
+    }
+
+    /// A copy of what the screen shows now, without its history or recorded
+    /// frames.
+    pub fn fork(&self) -> Screen {
+        Screen {
+            board: self.board.without_history(),
+            counts: self.counts,
+            count_history: vec![],
+            score: self.score,
+            score_history: vec![],
+            broken: vec![],
+            walls: self.walls,
+            ball: self.ball,
+            paddle: self.paddle,
+            frame: self.frame,
+        }
+    }
+
+    /// Keeps every frame of the board from now on.
+    pub fn record_frames(&mut self) {
+        self.board.record_frames();
+    }
+
+    /// Positions never drawn are empty.
+    pub fn tile(&self, x: i64, y: i64) -> Tile {
+        self.board.get(x, y).map_or(Tile::Empty, Tile::from_int)
+    }
+
+    /// Tiles of one type on the screen now.
+    pub fn count(&self, tile: Tile) -> usize {
+        self.counts[tile as usize]
+    }
+
+    /// The tile counts as each finished frame left them, skipping frames that
+    /// left them unchanged.
+    pub fn count_history(&self) -> &[(u64, TileCounts)] {
+        &self.count_history
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    /// Every new score, with the frame it was shown on.
+    pub fn score_history(&self) -> &[(u64, i64)] {
+        &self.score_history
+    }
+
+    /// Every block broken, with the frame it was broken on.
+    pub fn broken_blocks(&self) -> &[(u64, (i64, i64))] {
+        &self.broken
+    }
+
+    /// The smallest rectangle around every wall, or `None` before any are drawn.
+    pub fn walls(&self) -> Option<Bounds> {
+        self.walls
+    }
+
+    pub fn ball(&self) -> Option<(i64, i64)> {
+        self.ball
+    }
+
+    pub fn paddle(&self) -> Option<(i64, i64)> {
+        self.paddle
+    }
+}
+
+impl Extend<Message> for Screen {
+    fn extend<I: IntoIterator<Item = Message>>(&mut self, messages: I) {
+        for message in messages {
+            self.apply(message);
+        }
+    }
+}
+
+/// One line of tile glyphs per row within the walls, each ending in a newline.
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(bounds) = self.walls().or_else(|| self.board.bounds()).map(Bounds::clipped) {
+            for y in bounds.min_y..=bounds.max_y {
+                for x in bounds.min_x..=bounds.max_x {
+                    write!(f, "{}", self.tile(x, y))?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::game::{Game, State};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+/// The best play the solver found.
+#[derive(Debug, PartialEq)]
+pub struct Solution {
+    pub score: i64,
+    pub frames: u64,
+    /// Whether every block was broken. If not, the search ran out of frames
+    /// first, and this is the best score within them.
+    pub cleared: bool,
+    /// Whether every state was searched, so that the solution is the best
+    /// there is. Only a beam drops states.
+    pub exhaustive: bool,
+    /// The joystick moves from the start of the search.
+    pub moves: Vec<i64>,
+}
+
+/// A joystick move, and the move made on the frame before it.
+struct Move {
+    previous: Option<usize>,
+    joystick: i64,
+}
+
+/// Searches over joystick moves a frame at a time, for the fewest frames
+/// needed to clear the board, or failing that the highest score and the
+/// fewest frames needed to reach it. Every state is a fork of the cabinet.
+///
+/// Which blocks are broken decides the score, so games with the same `State`
+/// play out the same from then on, and only the first reached is searched.
+/// As the search goes breadth first, that is also one reached in the fewest
+/// frames.
+///
+/// Where the paddle meets the ball changes how it bounces, so the number of
+/// states grows quickly, and searching every one is only practical for a few
+/// hundred frames. With a `beam`, only that many of the most promising states
+/// are kept on each frame: highest score first, then fewest blocks left, then
+/// the paddle closest to the ball. That reaches much further, but the solution
+/// may not be the best.
+pub struct Solver {
+    max_frames: u64,
+    beam: Option<usize>,
+}
+
+impl Solver {
+    /// Gives up after searching `max_frames` frames ahead.
+    pub fn new(max_frames: u64) -> Solver {
+        Solver { max_frames, beam: None }
+    }
+
+    pub fn beam(mut self, width: usize) -> Solver {
+        self.beam = Some(width);
+        self
+    }
+
+    pub fn solve(&self, game: &Game) -> Solution {
+        let start = game.frames();
+        let mut seen: HashSet<State> = HashSet::new();
+        seen.insert(game.state());
+        let mut moves: Vec<Move> = vec![];
+        let mut frontier = vec![(game.fork(), None)];
+        // The score, frame, last move and whether the board was cleared
+        let mut best = (game.score(), start, None, game.blocks() == 0);
+        let mut exhaustive = true;
+
+        for _ in 0..self.max_frames {
+            let mut next = vec![];
+            for (state, previous) in &frontier {
+                for joystick in &[0, -1, 1] {
+                    let mut child = state.fork();
+                    child.step(*joystick);
+
+                    let lost = child.finished() && child.blocks() > 0;
+                    if !lost && seen.insert(child.state()) {
+                        moves.push(Move { previous: *previous, joystick: *joystick });
+                        next.push((child, Some(moves.len() - 1)));
+                    }
+                }
+            }
+
+            next.sort_by_key(|(game, _)| (Reverse(game.score()), game.blocks(), game.ball_offset().map_or(0, i64::abs)));
+            if let Some(beam) = self.beam.filter(|beam| next.len() > *beam) {
+                next.truncate(beam);
+                exhaustive = false;
+            }
+
+            let (leader, last_move) = match next.first() {
+                Some(leader) => leader,
+                None => break,
+            };
+            // A later state with the same score is no better
+            if leader.score() > best.0 || leader.blocks() == 0 {
+                best = (leader.score(), leader.frames(), *last_move, leader.blocks() == 0);
+            }
+            if leader.blocks() == 0 {
+                break;
+            }
+            frontier = next;
+        }
+
+        let (score, frames, last_move, cleared) = best;
+        Solution {
+            score,
+            frames: frames - start,
+            cleared,
+            exhaustive,
+            moves: Solver::moves(&moves, last_move),
+        }
+    }
+
+    /// The moves leading up to and including `last`.
+    fn moves(moves: &[Move], mut last: Option<usize>) -> Vec<i64> {
+        let mut sequence = vec![];
+        while let Some(index) = last {
+            sequence.push(moves[index].joystick);
+            last = moves[index].previous;
+        }
+        sequence.reverse();
+        sequence
+    }
+}
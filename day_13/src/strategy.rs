@@ -0,0 +1,170 @@
+use crate::game::Game;
+use console::{Key, Term};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Decides how to move the joystick on every frame: -1 for left, 0 for
+/// neutral and 1 for right.
+pub trait JoystickStrategy {
+    fn name(&self) -> &str;
+
+    fn joystick(&mut self, game: &Game) -> i64;
+}
+
+/// Keeps the paddle under the ball.
+pub struct FollowBall;
+
+impl JoystickStrategy for FollowBall {
+    fn name(&self) -> &str {
+        "follow"
+    }
+
+    fn joystick(&mut self, game: &Game) -> i64 {
+        game.ball_offset().map_or(0, i64::signum)
+    }
+}
+
+/// Runs a copy of the game ahead to see where the ball will come down, and
+/// moves the paddle there early.
+#[derive(Default)]
+pub struct Predictive {
+    /// The frame on which the ball next reaches the paddle's row, and where.
+    landing: Option<(u64, i64)>,
+}
+
+impl Predictive {
+    fn predict(game: &Game) -> Option<(u64, i64)> {
+        let row = game.paddle()?.1 - 1;
+        let mut future = game.fork();
+        while !future.finished() {
+            future.step(0);
+            if let Some((x, _)) = future.ball().filter(|(_, y)| *y == row) {
+                return Some((future.frames(), x));
+            }
+        }
+        None
+    }
+}
+
+impl JoystickStrategy for Predictive {
+    fn name(&self) -> &str {
+        "predict"
+    }
+
+    fn joystick(&mut self, game: &Game) -> i64 {
+        if self.landing.is_none_or(|(frame, _)| game.frames() > frame) {
+            self.landing = Predictive::predict(game);
+        }
+
+        let target = self.landing.map(|(_, x)| x).or_else(|| Some(game.ball()?.0));
+        match (target, game.paddle()) {
+            (Some(target), Some((paddle, _))) => (target - paddle).signum(),
+            _ => 0,
+        }
+    }
+}
+
+/// Moves with the arrow keys, or `a` and `d`. Any other key leaves the
+/// joystick in neutral.
+pub struct Keyboard {
+    terminal: Term,
+}
+
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Keyboard { terminal: Term::stdout() }
+    }
+}
+
+impl JoystickStrategy for Keyboard {
+    fn name(&self) -> &str {
+        "keyboard"
+    }
+
+    fn joystick(&mut self, _game: &Game) -> i64 {
+        match self.terminal.read_key().unwrap() {
+            Key::ArrowLeft | Key::Char('a') => -1,
+            Key::ArrowRight | Key::Char('d') => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Plays back a list of moves, then leaves the joystick in neutral.
+pub struct Replay {
+    moves: Vec<i64>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(moves: Vec<i64>) -> Replay {
+        Replay { moves, next: 0 }
+    }
+
+    /// Reads moves as written by `save_moves`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Replay> {
+        Ok(Replay::new(parse_moves(&fs::read_to_string(path)?)?))
+    }
+}
+
+/// Moves separated by commas or whitespace.
+pub fn parse_moves(text: &str) -> io::Result<Vec<i64>> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad move {:?}", word))))
+        .collect()
+}
+
+/// One line of comma separated moves.
+pub fn format_moves(moves: &[i64]) -> String {
+    let moves = moves.iter().map(|m| m.to_string()).collect::<Vec<String>>();
+    format!("{}\n", moves.join(","))
+}
+
+pub fn save_moves(path: impl AsRef<Path>, moves: &[i64]) -> io::Result<()> {
+    fs::write(path, format_moves(moves))
+}
+
+impl JoystickStrategy for Replay {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn joystick(&mut self, _game: &Game) -> i64 {
+        let joystick = self.moves.get(self.next).copied().unwrap_or(0);
+        self.next += 1;
+        joystick
+    }
+}
+
+/// How a strategy did over a whole game.
+#[derive(Debug, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    pub score: i64,
+    /// Frames played until the game ended.
+    pub frames: u64,
+    /// Frames on which the joystick was pushed left or right.
+    pub paddle_moves: usize,
+    /// Frames needed to break every block, if they all were.
+    pub cleared_at: Option<u64>,
+}
+
+/// Plays a headless game with each strategy.
+pub fn compare(program: &[i64], strategies: &mut [Box<dyn JoystickStrategy>]) -> Vec<Comparison> {
+    strategies
+        .iter_mut()
+        .map(|strategy| {
+            let mut game = Game::new(program);
+            let score = game.play(strategy.as_mut(), None);
+            Comparison {
+                name: strategy.name().to_string(),
+                score,
+                frames: game.frames(),
+                paddle_moves: game.moves().iter().filter(|joystick| **joystick != 0).count(),
+                cleared_at: game.cleared_at(),
+            }
+        })
+        .collect()
+}
@@ -1,4 +1,6 @@
-fn read_values(program_state: &Vec<i32>, op_pos: usize) -> (i32, i32, usize) {
+use intcode::{parse_program, Search};
+
+fn read_values(program_state: &[i32], op_pos: usize) -> (i32, i32, usize) {
     (
         program_state[program_state[op_pos + 1] as usize], 
         program_state[program_state[op_pos + 2] as usize], 
@@ -8,7 +10,7 @@ fn read_values(program_state: &Vec<i32>, op_pos: usize) -> (i32, i32, usize) {
 
 fn run_program(mut program_state: Vec<i32>) -> i32 {
 
-    let mut instr_ptr = 0 as usize;
+    let mut instr_ptr = 0_usize;
 
     loop {
         match program_state[instr_ptr] {
@@ -24,7 +26,7 @@ fn run_program(mut program_state: Vec<i32>) -> i32 {
             _ => panic!("Unexpected op_code")
         }
 
-        instr_ptr = instr_ptr + 4;
+        instr_ptr += 4;
     }
 
     program_state[0]
@@ -37,16 +39,14 @@ fn modify_initial_state(initial_state: Vec<i32>, addr_1_val: i32, addr_2_val: i3
     mod_state
 }
 
-fn find_target_value(initial_state: Vec<i32>, target_val: i32) -> (i32, i32) {
-    for noun in 0..=99 {
-        for verb in 0..=99 {
-            if run_program(modify_initial_state(initial_state.clone(), noun, verb)) == target_val {
-                return (noun, verb);
-            }
-        }
-    }
+fn find_target_value(program: &[i64], target_val: i64) -> (i64, i64) {
+    let found = Search::new(program)
+        .patch(1, 0..=99)
+        .patch(2, 0..=99)
+        .find_first(|outcome| outcome.memory[0] == target_val)
+        .expect("Failed to find target value!");
 
-    unreachable!("Failed to find target value!");
+    (found[0], found[1])
 }
 
 fn parse(input_str: &'static str) -> Vec<i32> {
@@ -59,7 +59,7 @@ fn main() {
     let program_state = modify_initial_state(initial_state.clone(), 12, 2);
     println!("Part 1 => {}", run_program(program_state));
 
-    let (noun, verb) = find_target_value(initial_state.clone(), 19690720);
+    let (noun, verb) = find_target_value(&parse_program(include_str!("../input/day_2.txt")).unwrap(), 19690720);
     println!("Part 2 => {}", 100 * noun + verb);
 }
 
@@ -72,7 +72,20 @@ fn part_1_complete() {
 
 #[test]
 fn part_2_complete() {
-    let initial_state = parse(include_str!("../input/day_2.txt"));
-    let (noun, verb) = find_target_value(initial_state.clone(), 19690720);
+    let (noun, verb) = find_target_value(&parse_program(include_str!("../input/day_2.txt")).unwrap(), 19690720);
     assert_eq!(100 * noun + verb, 3892);
-}
\ No newline at end of file
+}
+
+#[test]
+fn part_2_symbolic() {
+    let program = parse_program(include_str!("../input/day_2.txt")).unwrap();
+    let paths = intcode::Symbolic::new(&program)
+        .symbol(1, "noun")
+        .symbol(2, "verb")
+        .explore()
+        .unwrap();
+    let solution = intcode::solve_linear(&paths[0].memory[0], 19690720, &[("noun", 0..=99), ("verb", 0..=99)])
+        .unwrap()
+        .expect("No solution");
+    assert_eq!(100 * solution["noun"] + solution["verb"], 3892);
+}
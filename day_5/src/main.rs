@@ -1,7 +1,7 @@
 use intcode::*;
 
 fn main() {
-    let prog = parse_program(include_str!("../input/day_5.txt"));
+    let prog = program_from_args(include_str!("../input/day_5.txt"));
     println!(
         "Part 1 => {}",
         IntcodeComputer::new(&prog)
@@ -22,7 +22,7 @@ fn main() {
 #[test]
 fn part_1_complete() {
     assert_eq!(
-        *IntcodeComputer::new(&parse_program(include_str!("../input/day_5.txt")))
+        *IntcodeComputer::new(&parse_program(include_str!("../input/day_5.txt")).unwrap())
             .run(vec!(1))
             .last()
             .unwrap(),
@@ -33,10 +33,19 @@ fn part_1_complete() {
 #[test]
 fn part_2_complete() {
     assert_eq!(
-        *IntcodeComputer::new(&parse_program(include_str!("../input/day_5.txt")))
+        *IntcodeComputer::new(&parse_program(include_str!("../input/day_5.txt")).unwrap())
             .run(vec!(5))
             .last()
             .unwrap(),
         3419022
     );
 }
+
+#[test]
+fn reuses_first_instruction_as_scratch() {
+    let mut computer = IntcodeComputer::new(&parse_program(include_str!("../input/day_5.txt")).unwrap());
+    computer.track_self_modification();
+    computer.run(vec!(5));
+    assert!(computer.self_modifications().iter().all(|m| m.addr == 0));
+    assert_eq!(computer.self_modifications().len(), 2);
+}
@@ -27,7 +27,7 @@ impl Thrusters {
             .iter()
             .enumerate()
             .fold(0, |input, (i, setting)| {
-                *amps[i].run(vec![*setting, input]).last().unwrap()
+                amps[i].outputs(vec![*setting, input]).last().unwrap()
             });
 
         if !self.feedback_loop {
@@ -44,7 +44,7 @@ impl Thrusters {
                 .iter()
                 .enumerate()
                 .fold(last_output, |input, (i, _)| {
-                    *amps[i].run(vec![input]).last().unwrap_or(&input)
+                    amps[i].outputs(vec![input]).last().unwrap_or(input)
                 });
         }
     }
@@ -63,7 +63,7 @@ fn get_sequence_permutations(min: i64, max: i64) -> Vec<Vec<i64>> {
 }
 
 fn main() {
-    let prog = parse_program(include_str!("../input/day_7.txt"));
+    let prog = program_from_args(include_str!("../input/day_7.txt"));
     let max_val = get_sequence_permutations(0, 4)
         .iter()
         .map(|s| Thrusters::new(prog.clone(), s.clone(), false).run())
@@ -81,20 +81,20 @@ fn main() {
 
 #[test]
 fn thruster_signal() {
-    let mut prog = parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+    let mut prog = parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap();
     assert_eq!(
         Thrusters::new(prog, vec!(4, 3, 2, 1, 0), false).run(),
         43210
     );
 
     prog =
-        parse_program("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0");
+        parse_program("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0").unwrap();
     assert_eq!(
         Thrusters::new(prog, vec!(0, 1, 2, 3, 4), false).run(),
         54321
     );
 
-    prog = parse_program("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0");
+    prog = parse_program("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0").unwrap();
     assert_eq!(
         Thrusters::new(prog, vec!(1, 0, 4, 3, 2), false).run(),
         65210
@@ -103,7 +103,7 @@ fn thruster_signal() {
 
 #[test]
 fn part_1_complete() {
-    let prog = parse_program(include_str!("../input/day_7.txt"));
+    let prog = parse_program(include_str!("../input/day_7.txt")).unwrap();
     let max_val = get_sequence_permutations(0, 4)
         .iter()
         .map(|s| Thrusters::new(prog.clone(), s.clone(), false).run())
@@ -116,19 +116,20 @@ fn part_1_complete() {
 fn thruster_signal_feedback_loop() {
     let mut prog = parse_program(
         "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
-    );
+    )
+    .unwrap();
     assert_eq!(
         Thrusters::new(prog, vec!(9, 8, 7, 6, 5), true).run(),
         139629729
     );
 
-    prog = parse_program("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10");
+    prog = parse_program("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10").unwrap();
     assert_eq!(Thrusters::new(prog, vec!(9, 7, 8, 5, 6), true).run(), 18216);
 }
 
 #[test]
 fn part_2_complete() {
-    let prog = parse_program(include_str!("../input/day_7.txt"));
+    let prog = parse_program(include_str!("../input/day_7.txt")).unwrap();
 
     let max_feedback_val = get_sequence_permutations(5, 9)
         .iter()
@@ -1,7 +1,7 @@
 use intcode::*;
 
 fn main() {
-    let program = parse_program(include_str!("../input/day_9.txt"));
+    let program = program_from_args(include_str!("../input/day_9.txt"));
 
     println!(
         "Part 1 => {}",
@@ -21,7 +21,7 @@ fn main() {
 
 #[test]
 fn part_1_complete() {
-    let program = parse_program(include_str!("../input/day_9.txt"));
+    let program = parse_program(include_str!("../input/day_9.txt")).unwrap();
     assert_eq!(
         *IntcodeComputer::new(&program)
             .run(vec!(1))
@@ -33,7 +33,7 @@ fn part_1_complete() {
 
 #[test]
 fn part_2_complete() {
-    let program = parse_program(include_str!("../input/day_9.txt"));
+    let program = parse_program(include_str!("../input/day_9.txt")).unwrap();
     assert_eq!(
         *IntcodeComputer::new(&program)
             .run(vec!(2))
@@ -0,0 +1,59 @@
+//! Converts intcode programs between the text and binary formats. The input
+//! format is detected, and the output is the other one.
+//!
+//! Usage: convert [--no-checksum] <input> <output>
+//!
+//! Either path can be `-` for stdin or stdout.
+
+use intcode::{binary, read_program};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+fn usage() -> ! {
+    eprintln!("Usage: convert [--no-checksum] <input> <output>");
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("convert: {}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let mut with_checksum = true;
+    let mut paths = vec![];
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--no-checksum" => with_checksum = false,
+            "-h" | "--help" => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+    }
+
+    let mut bytes = vec![];
+    let read = if paths[0] == "-" {
+        io::stdin().read_to_end(&mut bytes)
+    } else {
+        File::open(&paths[0]).and_then(|mut file| file.read_to_end(&mut bytes))
+    };
+    read.unwrap_or_else(|err| fail(format!("could not read {}: {}", paths[0], err)));
+
+    let program = read_program(&bytes[..]).unwrap_or_else(|err| fail(format!("{}: {}", paths[0], err)));
+    let converted = if binary::is_binary(&bytes) {
+        let text = program.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+        format!("{}\n", text.join(",")).into_bytes()
+    } else {
+        binary::encode_program(&program, with_checksum)
+    };
+
+    let written = if paths[1] == "-" {
+        io::stdout().write_all(&converted)
+    } else {
+        File::create(&paths[1]).and_then(|mut file| file.write_all(&converted))
+    };
+    written.unwrap_or_else(|err| fail(format!("could not write {}: {}", paths[1], err)));
+}
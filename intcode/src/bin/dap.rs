@@ -0,0 +1,12 @@
+//! Debug Adapter Protocol server for intcode programs, talking over stdio.
+//! Point an editor's debug adapter configuration at this binary.
+
+use std::io;
+
+fn main() {
+    let stdin = io::stdin();
+    if let Err(err) = intcode::dap::serve(stdin.lock(), io::stdout()) {
+        eprintln!("dap: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,58 @@
+//! Full screen terminal debugger for intcode programs.
+//!
+//! Usage: debugger <program> [input...]
+
+use console::Term;
+use intcode::tui::{Command, Debugger};
+use intcode::{load_program, IntcodeComputer};
+use std::io;
+use std::process;
+
+fn main() -> io::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if args.is_empty() {
+        eprintln!("Usage: debugger <program> [input...]");
+        process::exit(2);
+    }
+
+    let program = load_program(&args[0]).unwrap_or_else(|err| {
+        eprintln!("debugger: could not load {}: {}", args[0], err);
+        process::exit(1);
+    });
+    let mut debugger = Debugger::new(IntcodeComputer::new(&program));
+    for input in &args[1..] {
+        match input.parse() {
+            Ok(input) => debugger.queue_input(input),
+            Err(_) => {
+                eprintln!("debugger: input {:?} is not a number", input);
+                process::exit(2);
+            }
+        }
+    }
+
+    let terminal = Term::stdout();
+    terminal.hide_cursor()?;
+    loop {
+        let (rows, columns) = terminal.size();
+        terminal.clear_screen()?;
+        terminal.write_str(debugger.render(columns as usize, rows as usize).trim_end())?;
+
+        match Command::from_key(&terminal.read_key()?) {
+            Some(Command::Quit) => break,
+            Some(Command::Input) => {
+                terminal.show_cursor()?;
+                terminal.write_str("\ninput> ")?;
+                let line = terminal.read_line()?;
+                terminal.hide_cursor()?;
+                if let Ok(input) = line.trim().parse() {
+                    debugger.queue_input(input);
+                }
+            }
+            Some(command) => debugger.apply(command),
+            None => {}
+        }
+    }
+
+    terminal.show_cursor()?;
+    terminal.clear_screen()
+}
@@ -0,0 +1,288 @@
+//! A compact binary encoding for programs and snapshots.
+//!
+//! Every file starts with a 11 byte header: the magic bytes `ICB1`, a format
+//! version, what the payload is (0 for a program, 1 for a snapshot), flags
+//! (bit 0 set when a checksum follows the payload), and the payload length in
+//! bytes as a little endian u32. Words in the payload are zigzag encoded
+//! LEB128 varints, so small values of either sign take a single byte. The
+//! optional checksum is the FNV-1a hash of the payload as a little endian u32.
+
+use crate::Snapshot;
+use std::convert::TryInto;
+use std::fmt;
+
+pub const MAGIC: [u8; 4] = *b"ICB1";
+pub const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 11;
+const KIND_PROGRAM: u8 = 0;
+const KIND_SNAPSHOT: u8 = 1;
+const FLAG_CHECKSUM: u8 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// The payload is not the kind that was asked for, such as a snapshot
+    /// where a program was expected.
+    WrongKind(u8),
+    /// The data ends before the header, payload or checksum does.
+    Truncated,
+    /// A varint is too long to fit in 64 bits.
+    Overflow,
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The payload has bytes left over after everything was decoded.
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a binary intcode file"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            DecodeError::WrongKind(kind) => write!(f, "unexpected payload kind {}", kind),
+            DecodeError::Truncated => write!(f, "data is truncated"),
+            DecodeError::Overflow => write!(f, "number does not fit in 64 bits"),
+            DecodeError::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum is {:08x}, expected {:08x}", actual, expected)
+            }
+            DecodeError::TrailingBytes => write!(f, "unexpected bytes after the payload"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Whether `bytes` look like the binary format rather than text.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+fn checksum(payload: &[u8]) -> u32 {
+    payload.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn write_unsigned(out: &mut Vec<u8>, mut val: u64) {
+    while val >= 0x80 {
+        out.push(val as u8 | 0x80);
+        val >>= 7;
+    }
+    out.push(val as u8);
+}
+
+fn write_word(out: &mut Vec<u8>, val: i64) {
+    write_unsigned(out, ((val << 1) ^ (val >> 63)) as u64);
+}
+
+fn write_words(out: &mut Vec<u8>, words: &[i64]) {
+    write_unsigned(out, words.len() as u64);
+    for word in words {
+        write_word(out, *word);
+    }
+}
+
+fn wrap(kind: u8, payload: Vec<u8>, with_checksum: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len() + 4);
+    out.extend_from_slice(&MAGIC);
+    out.push(VERSION);
+    out.push(kind);
+    out.push(if with_checksum { FLAG_CHECKSUM } else { 0 });
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&payload);
+    if with_checksum {
+        out.extend_from_slice(&checksum(&payload).to_le_bytes());
+    }
+    out
+}
+
+/// Checks the header and checksum and returns the payload.
+fn unwrap(bytes: &[u8], kind: u8) -> Result<&[u8], DecodeError> {
+    if bytes.len() < MAGIC.len() || !is_binary(bytes) {
+        return Err(DecodeError::BadMagic);
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(DecodeError::Truncated);
+    }
+    if bytes[4] != VERSION {
+        return Err(DecodeError::UnsupportedVersion(bytes[4]));
+    }
+    if bytes[5] != kind {
+        return Err(DecodeError::WrongKind(bytes[5]));
+    }
+
+    let with_checksum = bytes[6] & FLAG_CHECKSUM != 0;
+    let payload_len = u32::from_le_bytes(bytes[7..HEADER_LEN].try_into().unwrap()) as usize;
+    let total_len = HEADER_LEN + payload_len + if with_checksum { 4 } else { 0 };
+    if bytes.len() < total_len {
+        return Err(DecodeError::Truncated);
+    }
+    if bytes.len() > total_len {
+        return Err(DecodeError::TrailingBytes);
+    }
+
+    let payload = &bytes[HEADER_LEN..HEADER_LEN + payload_len];
+    if with_checksum {
+        let expected = u32::from_le_bytes(bytes[HEADER_LEN + payload_len..].try_into().unwrap());
+        let actual = checksum(payload);
+        if expected != actual {
+            return Err(DecodeError::ChecksumMismatch { expected, actual });
+        }
+    }
+    Ok(payload)
+}
+
+/// Reads varints from a payload.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn unsigned(&mut self) -> Result<u64, DecodeError> {
+        let mut val = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = self.bytes.split_first().ok_or(DecodeError::Truncated)?;
+            self.bytes = rest;
+            if shift == 63 && *byte > 1 {
+                return Err(DecodeError::Overflow);
+            }
+            val |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+        Err(DecodeError::Overflow)
+    }
+
+    fn word(&mut self) -> Result<i64, DecodeError> {
+        let val = self.unsigned()?;
+        Ok((val >> 1) as i64 ^ -((val & 1) as i64))
+    }
+
+    fn words(&mut self) -> Result<Vec<i64>, DecodeError> {
+        let len = self.unsigned()? as usize;
+        // Every word takes at least a byte, which bounds the allocation
+        if len > self.bytes.len() {
+            return Err(DecodeError::Truncated);
+        }
+        (0..len).map(|_| self.word()).collect()
+    }
+
+    fn finish(&self) -> Result<(), DecodeError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeError::TrailingBytes)
+        }
+    }
+}
+
+pub fn encode_program(program: &[i64], with_checksum: bool) -> Vec<u8> {
+    let mut payload = vec![];
+    write_words(&mut payload, program);
+    wrap(KIND_PROGRAM, payload, with_checksum)
+}
+
+pub fn decode_program(bytes: &[u8]) -> Result<Vec<i64>, DecodeError> {
+    let mut reader = Reader {
+        bytes: unwrap(bytes, KIND_PROGRAM)?,
+    };
+    let program = reader.words()?;
+    reader.finish()?;
+    Ok(program)
+}
+
+pub fn encode_snapshot(snapshot: &Snapshot, with_checksum: bool) -> Vec<u8> {
+    let mut payload = vec![];
+    write_unsigned(&mut payload, snapshot.pointer as u64);
+    write_word(&mut payload, snapshot.relative_base);
+    write_unsigned(&mut payload, snapshot.instructions_executed);
+    // 0 when not waiting, otherwise one more than the address
+    write_unsigned(&mut payload, snapshot.wait_for_input_addr.map_or(0, |addr| addr as u64 + 1));
+    payload.push(snapshot.finished as u8);
+    write_words(&mut payload, &snapshot.inputs);
+    write_words(&mut payload, &snapshot.memory);
+    wrap(KIND_SNAPSHOT, payload, with_checksum)
+}
+
+pub fn decode_snapshot(bytes: &[u8]) -> Result<Snapshot, DecodeError> {
+    let mut reader = Reader {
+        bytes: unwrap(bytes, KIND_SNAPSHOT)?,
+    };
+    let pointer = reader.unsigned()? as usize;
+    let relative_base = reader.word()?;
+    let instructions_executed = reader.unsigned()?;
+    let wait_for_input_addr = match reader.unsigned()? {
+        0 => None,
+        addr => Some(addr as usize - 1),
+    };
+    let finished = match reader.bytes.split_first() {
+        Some((flag, rest)) => {
+            reader.bytes = rest;
+            *flag != 0
+        }
+        None => return Err(DecodeError::Truncated),
+    };
+    let inputs = reader.words()?;
+    let memory = reader.words()?;
+    reader.finish()?;
+
+    Ok(Snapshot {
+        memory,
+        pointer,
+        relative_base,
+        instructions_executed,
+        inputs,
+        wait_for_input_addr,
+        finished,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, IntcodeComputer};
+
+    #[test]
+    fn program_round_trip() {
+        let program = vec![1, -1, 63, -64, 64, i64::MAX, i64::MIN, 0, 99];
+        for with_checksum in [false, true] {
+            let bytes = encode_program(&program, with_checksum);
+            assert_eq!(decode_program(&bytes), Ok(program.clone()));
+        }
+
+        let bytes = encode_program(&[1, -1, 64], false);
+        assert_eq!(bytes, b"ICB1\x01\x00\x00\x05\x00\x00\x00\x03\x02\x01\x80\x01");
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut computer = IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8").unwrap());
+        computer.step();
+        computer.queue_input(4);
+        let snapshot = computer.snapshot();
+        let decoded = decode_snapshot(&encode_snapshot(&snapshot, true)).unwrap();
+        assert_eq!(decoded, snapshot);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let bytes = encode_program(&[104, 7, 99], true);
+        assert_eq!(decode_program(b"1,2,99"), Err(DecodeError::BadMagic));
+        assert_eq!(decode_program(&bytes[..bytes.len() - 1]), Err(DecodeError::Truncated));
+        assert_eq!(decode_program(&[&bytes[..], &[0]].concat()), Err(DecodeError::TrailingBytes));
+        assert_eq!(decode_snapshot(&bytes), Err(DecodeError::WrongKind(KIND_PROGRAM)));
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + 1] ^= 1;
+        assert!(matches!(decode_program(&corrupted), Err(DecodeError::ChecksumMismatch { .. })));
+
+        let mut newer = bytes;
+        newer[4] = 2;
+        assert_eq!(decode_program(&newer), Err(DecodeError::UnsupportedVersion(2)));
+
+        let too_long = wrap(KIND_PROGRAM, vec![1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f], false);
+        assert_eq!(decode_program(&too_long), Err(DecodeError::Overflow));
+    }
+}
@@ -0,0 +1,368 @@
+//! An execution engine that compiles each basic block into a chain of closures,
+//! with the operand modes worked out once instead of on every execution.
+//!
+//! Every block `disasm::ControlFlow::recover` finds is compiled as soon as the
+//! engine is selected. Self-modifying code means code can't always be told from
+//! data up front, so any other block, such as one only reached through a jump
+//! computed at run time or one written later, is compiled the first time the
+//! pointer reaches it. Blocks are thrown away as soon as anything writes into
+//! them, and compiled again when next reached. Whatever cannot be compiled, such as
+//! registered opcodes, instructions running into a device's range, or words
+//! that would fault, is left to the interpreter, so both engines behave the
+//! same.
+
+use crate::disasm::{self, ControlFlow, Decoded, Op, Operand};
+use crate::{IntcodeComputer, Output};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// How an `IntcodeComputer` executes instructions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Engine {
+    /// Decodes every instruction as it runs.
+    #[default]
+    Interpreter,
+    /// Runs compiled basic blocks, falling back to the interpreter for anything
+    /// it cannot compile.
+    Compiled,
+}
+
+type Load = Box<dyn Fn(&mut IntcodeComputer) -> i64>;
+type Store = Box<dyn Fn(&mut IntcodeComputer) -> usize>;
+type Run = Box<dyn Fn(&mut IntcodeComputer) -> Option<Output>>;
+
+struct Instruction {
+    addr: usize,
+    length: usize,
+    run: Run,
+}
+
+struct Block {
+    instructions: Vec<Instruction>,
+}
+
+/// The compiled blocks of a computer.
+#[derive(Default)]
+pub(crate) struct Cache {
+    /// The block and index of the instruction starting at each address.
+    entries: Vec<Option<(Rc<Block>, usize)>>,
+    /// The start of the block each word was compiled into.
+    owners: Vec<Option<usize>>,
+    blocks: HashMap<usize, Range<usize>>,
+    /// Bumped whenever a block is thrown away, so a running block can tell.
+    generation: u64,
+}
+
+impl Cache {
+    pub(crate) fn clear(&mut self) {
+        let generation = self.generation + 1;
+        *self = Cache::default();
+        self.generation = generation;
+    }
+
+    fn owner(&self, addr: usize) -> Option<usize> {
+        self.owners.get(addr).copied().flatten()
+    }
+
+    /// Throws away the block covering `addr`, if there is one.
+    pub(crate) fn invalidate(&mut self, addr: usize) {
+        if let Some(start) = self.owner(addr) {
+            for word in self.blocks.remove(&start).unwrap() {
+                self.owners[word] = None;
+                self.entries[word] = None;
+            }
+            self.generation += 1;
+        }
+    }
+
+    fn insert(&mut self, start: usize, block: Block) -> Rc<Block> {
+        let block = Rc::new(block);
+        let end = block.instructions.last().map_or(start, |instr| instr.addr + instr.length);
+        if end > self.owners.len() {
+            self.owners.resize(end, None);
+            self.entries.resize(end, None);
+        }
+        for word in start..end {
+            self.owners[word] = Some(start);
+        }
+        for (index, instr) in block.instructions.iter().enumerate() {
+            self.entries[instr.addr] = Some((Rc::clone(&block), index));
+        }
+        self.blocks.insert(start, start..end);
+        block
+    }
+}
+
+fn load(operand: Operand) -> Load {
+    match operand {
+        Operand::Position(addr) => {
+            let addr = addr as usize;
+            Box::new(move |computer| computer.read(addr))
+        }
+        Operand::Immediate(val) => Box::new(move |_| val),
+        Operand::Relative(offset) => {
+            Box::new(move |computer| {
+                let addr = relative_address(computer, offset);
+                computer.read(addr)
+            })
+        }
+    }
+}
+
+/// `at` is the operand's own address, which immediate mode writes to.
+fn store(operand: Operand, at: usize) -> Store {
+    match operand {
+        Operand::Position(addr) => {
+            let addr = addr as usize;
+            Box::new(move |_| addr)
+        }
+        Operand::Immediate(_) => Box::new(move |_| at),
+        Operand::Relative(offset) => Box::new(move |computer| relative_address(computer, offset)),
+    }
+}
+
+fn relative_address(computer: &IntcodeComputer, offset: i64) -> usize {
+    let addr = computer.relative_base + offset;
+    if addr < 0 {
+        panic!("Negative address {}", addr);
+    }
+    addr as usize
+}
+
+fn binary(instr: &Decoded, op: fn(i64, i64) -> i64) -> Run {
+    let (a, b) = (load(instr.operands[0]), load(instr.operands[1]));
+    let dest = store(instr.operands[2], instr.addr + 3);
+    Box::new(move |computer| {
+        let (a, b) = (a(computer), b(computer));
+        let dest = dest(computer);
+        computer.write(dest, op(a, b));
+        None
+    })
+}
+
+fn jump(instr: &Decoded, when_nonzero: bool) -> Run {
+    let (condition, target) = (load(instr.operands[0]), load(instr.operands[1]));
+    Box::new(move |computer| {
+        let (condition, target) = (condition(computer), target(computer));
+        if (condition != 0) == when_nonzero {
+            computer.pointer = target as usize;
+        }
+        None
+    })
+}
+
+fn body(instr: &Decoded) -> Run {
+    match instr.op {
+        Op::Add => binary(instr, |a, b| a.checked_add(b).expect("Arithmetic overflow")),
+        Op::Multiply => binary(instr, |a, b| a.checked_mul(b).expect("Arithmetic overflow")),
+        Op::LessThan => binary(instr, |a, b| if a < b { 1 } else { 0 }),
+        Op::Equals => binary(instr, |a, b| if a == b { 1 } else { 0 }),
+        Op::Input => {
+            let dest = store(instr.operands[0], instr.addr + 1);
+            Box::new(move |computer| {
+                let dest = dest(computer);
+                match computer.inputs.pop_front() {
+                    Some(input) => {
+                        computer.record_input(input);
+                        computer.write(dest, input);
+                        None
+                    }
+                    None => {
+                        computer.wait_for_input_addr = Some(dest);
+                        Some(Output::WaitingForInput)
+                    }
+                }
+            })
+        }
+        Op::Output => {
+            let val = load(instr.operands[0]);
+            Box::new(move |computer| {
+                let val = val(computer);
+                computer.record_output(val);
+                Some(Output::OutputVal(val))
+            })
+        }
+        Op::JumpIfTrue => jump(instr, true),
+        Op::JumpIfFalse => jump(instr, false),
+        Op::AdjustBase => {
+            let offset = load(instr.operands[0]);
+            Box::new(move |computer| {
+                computer.relative_base += offset(computer);
+                None
+            })
+        }
+        Op::Halt => Box::new(|computer| {
+            computer.has_exited = true;
+            Some(Output::Exit)
+        }),
+    }
+}
+
+/// Wraps the body with the bookkeeping every instruction does before it runs.
+fn compile(instr: &Decoded) -> Instruction {
+    let (addr, length) = (instr.addr, instr.length());
+    let body = body(instr);
+    Instruction {
+        addr,
+        length,
+        run: Box::new(move |computer| {
+            computer.instruction_start = addr;
+            if let Some(tracker) = computer.self_modification.as_mut() {
+                (addr..addr + length).for_each(|word| tracker.mark_executed(word));
+            }
+            computer.pointer = addr + length;
+            computer.instructions_executed += 1;
+            body(computer)
+        }),
+    }
+}
+
+impl IntcodeComputer {
+    /// The instruction at `addr`, if it can be compiled. Anything the
+    /// interpreter would fault on is left to it, so it faults the same way.
+    fn compilable(&self, addr: usize) -> Option<Decoded> {
+        let instr = disasm::decode(&self.memory, addr)?;
+        let words = addr..addr + instr.length();
+        let negative_address = instr.operands.iter().any(|operand| match operand {
+            Operand::Position(addr) => *addr < 0,
+            _ => false,
+        });
+        let on_device = self
+            .devices
+            .iter()
+            .any(|(range, _)| range.start < words.end && words.start < range.end);
+
+        if words.end > self.memory.len() || negative_address || on_device {
+            None
+        } else {
+            Some(instr)
+        }
+    }
+
+    /// Compiles the block starting at `start`, up to the first jump or halt, or
+    /// to where it runs into another block.
+    fn compile_block(&mut self, start: usize) -> Option<Rc<Block>> {
+        let mut instructions = vec![];
+        let mut addr = start;
+        while let Some(instr) = self.compilable(addr) {
+            let words = addr..instr.next_addr();
+            if instructions.is_empty() {
+                // Entering the middle of another block's instruction
+                words.clone().for_each(|word| self.compiled.invalidate(word));
+            } else if words.clone().any(|word| self.compiled.owner(word).is_some()) {
+                break;
+            }
+
+            instructions.push(compile(&instr));
+            addr = words.end;
+            if let Op::JumpIfTrue | Op::JumpIfFalse | Op::Halt = instr.op {
+                break;
+            }
+        }
+
+        if instructions.is_empty() {
+            None
+        } else {
+            Some(self.compiled.insert(start, Block { instructions }))
+        }
+    }
+
+    /// Compiles every block reachable from address 0, last first so that each
+    /// stops where the one after it starts.
+    pub(crate) fn precompile(&mut self) {
+        let starts = ControlFlow::recover(&self.memory).blocks.keys().copied().collect::<Vec<usize>>();
+        for start in starts.into_iter().rev() {
+            if self.compiled.owner(start).is_none() {
+                self.compile_block(start);
+            }
+        }
+    }
+
+    /// Runs up to `limit` compiled instructions from the pointer, stopping early
+    /// at the end of the block, on any output, or if the block is thrown away.
+    /// Returns `None` if there is nothing compiled to run.
+    pub(crate) fn run_compiled(&mut self, limit: usize) -> Option<Option<Output>> {
+        let entry = self.compiled.entries.get(self.pointer).cloned().flatten();
+        let (block, first) = match entry {
+            Some(entry) => entry,
+            None => (self.compile_block(self.pointer)?, 0),
+        };
+
+        let generation = self.compiled.generation;
+        for instr in block.instructions[first..].iter().take(limit) {
+            if self.pointer != instr.addr || self.compiled.generation != generation {
+                break;
+            }
+            if let Some(output) = (instr.run)(self) {
+                return Some(Some(output));
+            }
+        }
+
+        Some(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn both_engines(program: &str, inputs: Vec<i64>) -> (Vec<i64>, IntcodeComputer) {
+        let program = parse_program(program).unwrap();
+        let mut outputs = vec![];
+        let mut computers = vec![];
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&program);
+            computer.set_engine(*engine);
+            outputs.push(computer.run(inputs.clone()));
+            computers.push(computer);
+        }
+
+        let compiled = computers.pop().unwrap();
+        let interpreted = computers.pop().unwrap();
+        assert_eq!(outputs[1], outputs[0]);
+        assert_eq!(compiled.memory(), interpreted.memory());
+        assert_eq!(compiled.instruction_count(), interpreted.instruction_count());
+        (outputs.pop().unwrap(), compiled)
+    }
+
+    #[test]
+    fn runs_like_the_interpreter() {
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        assert_eq!(both_engines(quine, vec![]).0, parse_program(quine).unwrap());
+
+        let compare = "3,3,1107,-1,8,3,4,3,99";
+        assert_eq!(both_engines(compare, vec![7]).0, vec![1]);
+    }
+
+    #[test]
+    fn writes_invalidate_blocks() {
+        // The first add rewrites an operand of the second, in the same block,
+        // so it stores 4 rather than 7 before it is output
+        let (outputs, _) = both_engines("1101,0,4,6,1101,0,7,13,4,13,99,0,0,0", vec![]);
+        assert_eq!(outputs, vec![4]);
+
+        let mut computer = IntcodeComputer::new(&parse_program("1101,1,1,20,1105,1,0").unwrap());
+        computer.set_engine(Engine::Compiled);
+        computer.step();
+        assert_eq!(computer.compiled.blocks.len(), 1);
+        computer.poke(5, 9);
+        assert!(computer.compiled.blocks.is_empty());
+    }
+
+    #[test]
+    fn selecting_the_engine_compiles_reachable_blocks() {
+        // Both sides of the conditional jump are compiled before anything
+        // runs, and each stops where the next starts
+        let program = "3,11,1005,11,7,104,0,104,1,99";
+        let mut computer = IntcodeComputer::new(&parse_program(program).unwrap());
+        computer.set_engine(Engine::Compiled);
+        let mut blocks = computer.compiled.blocks.values().cloned().collect::<Vec<_>>();
+        blocks.sort_by_key(|range| range.start);
+        assert_eq!(blocks, vec![0..5, 5..7, 7..10]);
+
+        assert_eq!(both_engines(program, vec![0]).0, vec![0, 1]);
+        assert_eq!(both_engines(program, vec![1]).0, vec![1]);
+    }
+}
@@ -0,0 +1,574 @@
+//! A Debug Adapter Protocol server, so any editor with DAP support can debug
+//! intcode programs.
+//!
+//! `launch` takes the path of a program (text or binary), the `inputs` to give
+//! it and whether to `stopOnEntry`. Breakpoints are set on addresses with
+//! `setInstructionBreakpoints`, or on the labels `disasm` gives jump targets,
+//! such as `func_12` or `label_40`, with `setFunctionBreakpoints`. A function
+//! breakpoint can also just be an address. There is a single thread with a
+//! single frame, whose scopes hold the registers and the outputs so far;
+//! `readMemory` reads memory as little endian 64 bit words, with word
+//! addresses as memory references, and reports bytes past the end of memory as
+//! unreadable. `disassemble` returns at most `DISASSEMBLE_LIMIT` instructions
+//! and stops at the last address. Evaluating `input N` queues another input.
+
+use crate::disasm::{self, ControlFlow, Flow};
+use crate::{load_program, IntcodeComputer, Output};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+
+const THREAD_ID: i64 = 1;
+const FRAME_ID: i64 = 1;
+const REGISTERS_REF: i64 = 1;
+const OUTPUTS_REF: i64 = 2;
+
+/// How far `continue` runs before giving control back, so a program stuck in
+/// a loop cannot hang the session.
+const CONTINUE_LIMIT: u64 = 10_000_000;
+
+/// The most instructions one `disassemble` request returns, however many it
+/// asks for.
+const DISASSEMBLE_LIMIT: u64 = 10_000;
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Reads one `Content-Length` framed message. Returns `None` at the end of input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() && length.is_some() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>().map_err(invalid_data)?);
+        }
+    }
+
+    let mut body = vec![0; length.unwrap()];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(invalid_data)
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0_u32, |bits, (i, b)| bits | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// What happened when the program was stepped.
+enum Event {
+    Stepped,
+    Output(i64),
+    Starved,
+    Halted,
+}
+
+/// A launched program.
+struct Session {
+    computer: IntcodeComputer,
+    outputs: Vec<i64>,
+    /// Every label `disasm` found, by name.
+    labels: BTreeMap<String, usize>,
+    entry_points: BTreeSet<usize>,
+    stop_on_entry: bool,
+}
+
+impl Session {
+    fn launch(args: &Value) -> Result<Session, String> {
+        let path = args["program"].as_str().ok_or("launch needs a program path")?;
+        let program = load_program(path).map_err(|err| format!("Could not load {}: {}", path, err))?;
+
+        let mut computer = IntcodeComputer::new(&program);
+        for input in args["inputs"].as_array().into_iter().flatten() {
+            computer.queue_input(input.as_i64().ok_or("inputs must be integers")?);
+        }
+
+        let control_flow = ControlFlow::recover(&program);
+        let mut labels = BTreeMap::new();
+        for block in control_flow.blocks.values() {
+            match block.flow {
+                Flow::Jump(target) | Flow::Branch(target) => {
+                    labels.insert(format!("label_{}", target), target);
+                }
+                Flow::Call { ret, .. } => {
+                    labels.insert(format!("label_{}", ret), ret);
+                }
+                _ => {}
+            }
+        }
+        for entry in &control_flow.entry_points {
+            labels.insert(format!("func_{}", entry), *entry);
+        }
+
+        Ok(Session {
+            computer,
+            outputs: vec![],
+            labels,
+            entry_points: control_flow.entry_points,
+            stop_on_entry: args["stopOnEntry"].as_bool().unwrap_or(false),
+        })
+    }
+
+    fn step(&mut self) -> Event {
+        match self.computer.step() {
+            Some(Output::OutputVal(val)) => {
+                self.outputs.push(val);
+                Event::Output(val)
+            }
+            Some(Output::WaitingForInput) => Event::Starved,
+            Some(Output::Exit) => Event::Halted,
+            None => Event::Stepped,
+        }
+    }
+
+    fn pointer(&self) -> usize {
+        self.computer.pointer
+    }
+
+    /// The routine the pointer is in, going by the closest entry point before it.
+    fn routine(&self) -> String {
+        self.entry_points
+            .range(..=self.pointer())
+            .next_back()
+            .map_or("main".to_string(), |entry| format!("func_{}", entry))
+    }
+
+    fn resolve(&self, name: &str) -> Option<usize> {
+        name.trim().parse().ok().or_else(|| self.labels.get(name.trim()).copied())
+    }
+}
+
+pub struct Server<W: Write> {
+    output: W,
+    seq: i64,
+    session: Option<Session>,
+    configured: bool,
+    function_breakpoints: Vec<String>,
+    instruction_breakpoints: BTreeSet<usize>,
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(output: W) -> Server<W> {
+        Server {
+            output,
+            seq: 0,
+            session: None,
+            configured: false,
+            function_breakpoints: vec![],
+            instruction_breakpoints: BTreeSet::new(),
+        }
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<&str>) -> io::Result<()> {
+        let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(description) = description {
+            body["description"] = json!(description);
+        }
+        self.event("stopped", body)
+    }
+
+    fn breakpoints(&self) -> BTreeSet<usize> {
+        let mut breakpoints = self.instruction_breakpoints.clone();
+        if let Some(session) = &self.session {
+            breakpoints.extend(self.function_breakpoints.iter().filter_map(|name| session.resolve(name)));
+        }
+        breakpoints
+    }
+
+    /// Reports what a step did. Returns whether the program can keep running.
+    fn report(&mut self, event: Event) -> io::Result<bool> {
+        match event {
+            Event::Stepped => Ok(true),
+            Event::Output(val) => {
+                self.event("output", json!({ "category": "stdout", "output": format!("{}\n", val) }))?;
+                Ok(true)
+            }
+            Event::Starved => {
+                self.stopped("pause", Some("Waiting for input"))?;
+                Ok(false)
+            }
+            Event::Halted => {
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                self.event("terminated", json!({}))?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Runs until a breakpoint, the program halts or needs input, or the limit.
+    /// When `resuming`, the program is already stopped at the pointer, so a
+    /// breakpoint there does not stop it again.
+    fn run(&mut self, resuming: bool) -> io::Result<()> {
+        let breakpoints = self.breakpoints();
+        for executed in 0..CONTINUE_LIMIT {
+            let session = self.session.as_mut().unwrap();
+            let at_breakpoint = !session.computer.waiting_for_input() && breakpoints.contains(&session.pointer());
+            if (executed > 0 || !resuming) && at_breakpoint {
+                return self.stopped("breakpoint", None);
+            }
+
+            let event = session.step();
+            if !self.report(event)? {
+                return Ok(());
+            }
+        }
+
+        self.stopped("pause", Some("Step limit reached"))
+    }
+
+    /// Starts the program once it is both launched and configured.
+    fn start(&mut self) -> io::Result<()> {
+        match &self.session {
+            Some(session) if self.configured => {
+                if session.stop_on_entry {
+                    self.stopped("entry", None)
+                } else {
+                    self.run(false)
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn session(&self) -> Result<&Session, String> {
+        self.session.as_ref().ok_or_else(|| "No program launched".to_string())
+    }
+
+    /// The session, as long as its program has not halted.
+    fn running_session(&mut self) -> Result<&mut Session, String> {
+        match self.session.as_mut() {
+            Some(session) if session.computer.finished() => Err("The program has exited".to_string()),
+            Some(session) => Ok(session),
+            None => Err("No program launched".to_string()),
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let session = self.session()?;
+        let pointer = session.pointer();
+        let instruction = disasm::decode(session.computer.memory(), pointer)
+            .map_or("??".to_string(), |instr| instr.to_string());
+        Ok(json!({
+            "stackFrames": [{
+                "id": FRAME_ID,
+                "name": format!("{} @ {}: {}", session.routine(), pointer, instruction),
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": pointer.to_string(),
+            }],
+            "totalFrames": 1,
+        }))
+    }
+
+    fn variables(&self, args: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        let variable = |name: &str, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+        let variables = match args["variablesReference"].as_i64() {
+            Some(REGISTERS_REF) => {
+                let computer = &session.computer;
+                let mut pointer = variable("pointer", computer.pointer.to_string());
+                pointer["memoryReference"] = json!(computer.pointer.to_string());
+                let inputs = computer.inputs.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+                vec![
+                    pointer,
+                    variable("relative_base", computer.relative_base.to_string()),
+                    variable("instructions", computer.instruction_count().to_string()),
+                    variable("waiting_for_input", computer.waiting_for_input().to_string()),
+                    variable("inputs", format!("[{}]", inputs.join(", "))),
+                ]
+            }
+            Some(OUTPUTS_REF) => session
+                .outputs
+                .iter()
+                .enumerate()
+                .map(|(i, val)| variable(&format!("[{}]", i), val.to_string()))
+                .collect(),
+            _ => return Err("Unknown variables reference".to_string()),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn read_memory(&self, args: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        let base = args["memoryReference"]
+            .as_str()
+            .and_then(|reference| reference.parse::<i64>().ok())
+            .ok_or("memoryReference must be an address")?;
+        let offset = args["offset"].as_i64().unwrap_or(0);
+        let start = base
+            .checked_mul(8)
+            .and_then(|start| start.checked_add(offset))
+            .ok_or("memoryReference and offset are out of range")?
+            .max(0) as usize;
+
+        // Bytes past the end of memory are unreadable rather than zeros
+        let end = session.computer.memory().len() * 8;
+        let requested = args["count"].as_u64().unwrap_or(0);
+        let count = usize::try_from(requested).unwrap_or(usize::MAX).min(end.saturating_sub(start));
+        let bytes = (start..start + count)
+            .map(|byte| session.computer.peek(byte / 8).to_le_bytes()[byte % 8])
+            .collect::<Vec<u8>>();
+        Ok(json!({
+            "address": (start / 8).to_string(),
+            "data": base64(&bytes),
+            "unreadableBytes": requested - count as u64,
+        }))
+    }
+
+    fn disassemble(&self, args: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        let memory = session.computer.memory();
+        let base = args["memoryReference"]
+            .as_str()
+            .and_then(|reference| reference.parse::<i64>().ok())
+            .ok_or("memoryReference must be an address")?;
+        let mut addr = base
+            .checked_add(args["instructionOffset"].as_i64().unwrap_or(0))
+            .ok_or("memoryReference and instructionOffset are out of range")?;
+        let count = args["instructionCount"].as_u64().unwrap_or(0).min(DISASSEMBLE_LIMIT);
+
+        let mut instructions = vec![];
+        for _ in 0..count {
+            let (text, length) = match disasm::decode(memory, addr.max(0) as usize) {
+                Some(instr) if addr >= 0 => (instr.to_string(), instr.length()),
+                _ => (format!("data {}", session.computer.peek(addr.max(0) as usize)), 1),
+            };
+            instructions.push(json!({ "address": addr.to_string(), "instruction": text }));
+            addr = match addr.checked_add(length as i64) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        Ok(json!({ "instructions": instructions }))
+    }
+
+    fn evaluate(&mut self, args: &Value) -> Result<Value, String> {
+        let expression = args["expression"].as_str().unwrap_or("").trim();
+        let value = expression
+            .strip_prefix("input ")
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .ok_or("Only `input N` can be evaluated")?;
+        let session = self.session.as_mut().ok_or("No program launched")?;
+        session.computer.queue_input(value);
+        Ok(json!({ "result": format!("queued {}", value), "variablesReference": 0 }))
+    }
+
+    /// Handles one request. Returns false once the client has disconnected.
+    pub fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let args = &request["arguments"];
+        match request["command"].as_str().unwrap_or("") {
+            "initialize" => {
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsFunctionBreakpoints": true,
+                    "supportsInstructionBreakpoints": true,
+                    "supportsReadMemoryRequest": true,
+                    "supportsDisassembleRequest": true,
+                });
+                self.respond(request, Ok(capabilities))?;
+                self.event("initialized", json!({}))?;
+            }
+            "launch" => match Session::launch(args) {
+                Ok(session) => {
+                    self.session = Some(session);
+                    self.respond(request, Ok(json!({})))?;
+                    self.start()?;
+                }
+                Err(message) => self.respond(request, Err(message))?,
+            },
+            "setFunctionBreakpoints" => {
+                self.function_breakpoints = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["name"].as_str().map(str::to_string))
+                    .collect();
+                let resolved = self
+                    .function_breakpoints
+                    .iter()
+                    .map(|name| self.session.as_ref().and_then(|session| session.resolve(name)))
+                    .map(|addr| match addr {
+                        Some(addr) => json!({ "verified": true, "instructionReference": addr.to_string() }),
+                        None => json!({ "verified": false }),
+                    })
+                    .collect::<Vec<Value>>();
+                self.respond(request, Ok(json!({ "breakpoints": resolved })))?;
+            }
+            "setInstructionBreakpoints" => {
+                let addresses = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|breakpoint| {
+                        let reference = breakpoint["instructionReference"].as_str()?.parse::<i64>().ok()?;
+                        let addr = reference + breakpoint["offset"].as_i64().unwrap_or(0);
+                        Some(addr).filter(|addr| *addr >= 0).map(|addr| addr as usize)
+                    })
+                    .collect::<Vec<Option<usize>>>();
+                self.instruction_breakpoints = addresses.iter().flatten().copied().collect();
+                let resolved = addresses
+                    .iter()
+                    .map(|addr| json!({ "verified": addr.is_some() }))
+                    .collect::<Vec<Value>>();
+                self.respond(request, Ok(json!({ "breakpoints": resolved })))?;
+            }
+            "configurationDone" => {
+                self.configured = true;
+                self.respond(request, Ok(json!({})))?;
+                self.start()?;
+            }
+            "threads" => {
+                let threads = json!({ "threads": [{ "id": THREAD_ID, "name": "intcode" }] });
+                self.respond(request, Ok(threads))?;
+            }
+            "stackTrace" => {
+                let trace = self.stack_trace();
+                self.respond(request, trace)?;
+            }
+            "scopes" => {
+                let scopes = json!({ "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_REF, "expensive": false },
+                    { "name": "Outputs", "variablesReference": OUTPUTS_REF, "expensive": false },
+                ]});
+                self.respond(request, Ok(scopes))?;
+            }
+            "variables" => {
+                let variables = self.variables(args);
+                self.respond(request, variables)?;
+            }
+            "readMemory" => {
+                let memory = self.read_memory(args);
+                self.respond(request, memory)?;
+            }
+            "disassemble" => {
+                let instructions = self.disassemble(args);
+                self.respond(request, instructions)?;
+            }
+            "evaluate" => {
+                let result = self.evaluate(args);
+                self.respond(request, result)?;
+            }
+            "continue" => match self.running_session() {
+                Ok(_) => {
+                    self.respond(request, Ok(json!({ "allThreadsContinued": true })))?;
+                    self.run(true)?;
+                }
+                Err(message) => self.respond(request, Err(message))?,
+            },
+            "next" | "stepIn" | "stepOut" => match self.running_session().map(Session::step) {
+                Ok(event) => {
+                    self.respond(request, Ok(json!({})))?;
+                    if self.report(event)? {
+                        self.stopped("step", None)?;
+                    }
+                }
+                Err(message) => self.respond(request, Err(message))?,
+            },
+            "pause" => {
+                // Requests are handled one at a time, so the program is never
+                // running when this arrives
+                self.respond(request, Ok(json!({})))?;
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})))?;
+                return Ok(false);
+            }
+            command => {
+                let message = format!("Unsupported request {}", command);
+                self.respond(request, Err(message))?;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Serves one debugging session, reading requests from `input` and writing
+/// responses and events to `output`.
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<()> {
+    let mut server = Server::new(output);
+    while let Some(request) = read_message(&mut input)? {
+        if !server.handle(&request)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&2_i64.to_le_bytes()), "AgAAAAAAAAA=");
+    }
+
+    #[test]
+    fn message_framing() {
+        let mut framed = vec![];
+        write_message(&mut framed, &json!({ "command": "threads" })).unwrap();
+        assert_eq!(framed, b"Content-Length: 21\r\n\r\n{\"command\":\"threads\"}");
+
+        let mut input = &framed[..];
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "command": "threads" })));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
@@ -0,0 +1,429 @@
+//! Turns intcode back into C-like pseudo-code, one routine per entry point
+//! found by `disasm`. Branches that fit the shapes compilers produce become
+//! `if`/`else`, `while` and `do`/`while`; anything else is left as a `goto`.
+//! Relative base slots are shown as locals, `local2` for `[rb+2]` and
+//! `local_m2` for `[rb-2]`.
+
+use crate::disasm::{BasicBlock, ControlFlow, Decoded, Flow, Op, Operand};
+use std::collections::BTreeSet;
+
+enum Stmt {
+    Label(usize),
+    Line(String),
+    If {
+        cond: String,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    While {
+        cond: String,
+        body: Vec<Stmt>,
+    },
+    DoWhile {
+        body: Vec<Stmt>,
+        cond: String,
+    },
+}
+
+fn operand(op: Operand) -> String {
+    match op {
+        Operand::Position(addr) => format!("mem[{}]", addr),
+        Operand::Immediate(val) => val.to_string(),
+        Operand::Relative(offset) if offset < 0 => format!("local_m{}", -offset),
+        Operand::Relative(offset) => format!("local{}", offset),
+    }
+}
+
+/// Where parameter `n` of `instr` writes to. An immediate mode write goes to
+/// the parameter itself.
+fn destination(instr: &Decoded, n: usize) -> String {
+    match instr.operands[n] {
+        Operand::Immediate(_) => format!("mem[{}]", instr.addr + 1 + n),
+        op => operand(op),
+    }
+}
+
+/// The value computed by an arithmetic or comparison instruction, with adding
+/// 0 and multiplying by 1 or 0 folded away.
+fn expression(instr: &Decoded) -> String {
+    use Operand::Immediate;
+
+    let (a, b) = (instr.operands[0], instr.operands[1]);
+    match (instr.op, a, b) {
+        (Op::Add, Immediate(x), Immediate(y)) if x.checked_add(y).is_some() => (x + y).to_string(),
+        (Op::Add, Immediate(0), x) | (Op::Add, x, Immediate(0)) => operand(x),
+        (Op::Add, x, Immediate(y)) if y < 0 && y != i64::MIN => format!("{} - {}", operand(x), -y),
+        (Op::Add, x, y) => format!("{} + {}", operand(x), operand(y)),
+        (Op::Multiply, Immediate(x), Immediate(y)) if x.checked_mul(y).is_some() => (x * y).to_string(),
+        (Op::Multiply, Immediate(0), _) | (Op::Multiply, _, Immediate(0)) => "0".to_string(),
+        (Op::Multiply, Immediate(1), x) | (Op::Multiply, x, Immediate(1)) => operand(x),
+        (Op::Multiply, x, y) => format!("{} * {}", operand(x), operand(y)),
+        (Op::LessThan, x, y) => format!("{} < {}", operand(x), operand(y)),
+        (_, x, y) => format!("{} == {}", operand(x), operand(y)),
+    }
+}
+
+fn statement(instr: &Decoded) -> Option<String> {
+    let line = match instr.op {
+        Op::Add | Op::Multiply | Op::LessThan | Op::Equals => {
+            format!("{} = {};", destination(instr, 2), expression(instr))
+        }
+        Op::Input => format!("{} = input();", destination(instr, 0)),
+        Op::Output => format!("output({});", operand(instr.operands[0])),
+        Op::AdjustBase => match instr.operands[0] {
+            Operand::Immediate(n) if n < 0 && n != i64::MIN => format!("rb -= {};", -n),
+            op => format!("rb += {};", operand(op)),
+        },
+        // Jumps are handled by the block they end, and one that is never
+        // taken does nothing.
+        Op::JumpIfTrue | Op::JumpIfFalse => return None,
+        Op::Halt => "halt;".to_string(),
+    };
+    Some(line)
+}
+
+/// The condition under which the jump ending `block` is taken, or not taken.
+fn condition(block: &BasicBlock, taken: bool) -> String {
+    let jump = block.instructions.last().unwrap();
+    let nonzero = (jump.op == Op::JumpIfTrue) == taken;
+    format!("{} {} 0", operand(jump.operands[0]), if nonzero { "!=" } else { "==" })
+}
+
+/// The jump at the end of `block`, whose target is only known at run time.
+fn indirect_jump(block: &BasicBlock) -> String {
+    match block.instructions.last().unwrap().operands[1] {
+        Operand::Relative(0) => "return;".to_string(),
+        target => format!("goto *{};", operand(target)),
+    }
+}
+
+/// Structures the blocks of one routine.
+struct Structurer<'a> {
+    blocks: Vec<&'a BasicBlock>,
+    /// Loop headers already turned into loops, so they are not again when
+    /// their own body is structured.
+    loops: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+}
+
+impl<'a> Structurer<'a> {
+    /// Index of the block at `addr`, if it is one of `lo..=hi`.
+    fn index_of(&self, addr: usize, lo: usize, hi: usize) -> Option<usize> {
+        (lo..=hi).find(|&i| self.blocks.get(i).map(|b| b.start) == Some(addr))
+    }
+
+    fn starts_at(&self, i: usize, addr: usize) -> bool {
+        self.blocks.get(i).map(|b| b.start) == Some(addr)
+    }
+
+    fn goto(&mut self, addr: usize) -> Stmt {
+        self.gotos.insert(addr);
+        Stmt::Line(format!("goto label_{};", addr))
+    }
+
+    /// The statements of block `i` up to its final jump.
+    fn straight(&self, i: usize) -> Vec<Stmt> {
+        let block = self.blocks[i];
+        let mut instructions = &block.instructions[..];
+        if let Flow::Call { .. } = block.flow {
+            // Storing the return address is part of the call
+            instructions = &instructions[..instructions.len().saturating_sub(2)];
+        }
+        instructions
+            .iter()
+            .filter(|instr| instr.op != Op::Halt)
+            .filter_map(statement)
+            .map(Stmt::Line)
+            .collect()
+    }
+
+    /// Where control goes after block `i` when it is not part of a structure.
+    fn terminator(&mut self, i: usize, out: &mut Vec<Stmt>) {
+        let block = self.blocks[i];
+        let fall_through = |s: &mut Self, out: &mut Vec<Stmt>, addr: usize| {
+            if !s.starts_at(i + 1, addr) {
+                out.push(s.goto(addr));
+            }
+        };
+
+        match block.flow {
+            Flow::Next => fall_through(self, out, block.end()),
+            Flow::Jump(target) => fall_through(self, out, target),
+            Flow::Branch(taken) => {
+                self.gotos.insert(taken);
+                out.push(Stmt::Line(format!(
+                    "if ({}) goto label_{};",
+                    condition(block, true),
+                    taken
+                )));
+                fall_through(self, out, block.end());
+            }
+            Flow::Call { target, ret } => {
+                out.push(Stmt::Line(format!("call func_{}();", target)));
+                fall_through(self, out, ret);
+            }
+            Flow::Indirect => out.push(Stmt::Line(indirect_jump(block))),
+            Flow::IndirectBranch => {
+                out.push(Stmt::Line(format!("if ({}) {}", condition(block, true), indirect_jump(block))));
+                fall_through(self, out, block.end());
+            }
+            Flow::Halt => out.push(Stmt::Line("halt;".to_string())),
+        }
+    }
+
+    /// Structures blocks `lo..hi`. With `open_end`, the final jump of the last
+    /// block belongs to an enclosing structure and is left out.
+    fn region(&mut self, lo: usize, hi: usize, open_end: bool) -> Vec<Stmt> {
+        let mut out = vec![];
+        let mut i = lo;
+        while i < hi {
+            let block = self.blocks[i];
+            if !self.loops.contains(&block.start) {
+                out.push(Stmt::Label(block.start));
+            }
+
+            // A later block branching back here closes a do/while loop
+            let back_branch = (i..hi).rev().find(|&k| self.blocks[k].flow == Flow::Branch(block.start));
+            if let Some(k) = back_branch.filter(|_| self.loops.insert(block.start)) {
+                let body = self.region(i, k + 1, true);
+                out.push(Stmt::DoWhile {
+                    body,
+                    cond: condition(self.blocks[k], true),
+                });
+                i = k + 1;
+                continue;
+            }
+
+            let forward = match block.flow {
+                Flow::Branch(taken) if taken > block.start => self.index_of(taken, i + 1, hi),
+                _ => None,
+            };
+            if open_end && i + 1 == hi || forward.is_none() {
+                out.extend(self.straight(i));
+                if !(open_end && i + 1 == hi) {
+                    self.terminator(i, &mut out);
+                }
+                i += 1;
+                continue;
+            }
+
+            // Branching forward past blocks `i + 1..m` and then joining at `m`
+            let m = forward.unwrap();
+            let tail = self.blocks[m - 1].flow;
+            if m > i + 1 && tail == Flow::Jump(block.start) {
+                // The body jumps back to the test: a while loop
+                self.loops.insert(block.start);
+                let mut body = self.straight(i);
+                let cond = if body.is_empty() {
+                    condition(block, false)
+                } else {
+                    body.push(Stmt::Line(format!("if ({}) break;", condition(block, true))));
+                    "true".to_string()
+                };
+                body.extend(self.region(i + 1, m, true));
+                out.push(Stmt::While { cond, body });
+                i = m;
+                continue;
+            }
+
+            out.extend(self.straight(i));
+            let cond = condition(block, false);
+            let else_end = match tail {
+                Flow::Jump(join) if m > i + 1 && join > self.blocks[m].start => self.index_of(join, m + 1, hi),
+                _ => None,
+            };
+            match else_end {
+                Some(n) => {
+                    let then = self.region(i + 1, m, true);
+                    let otherwise = self.region(m, n, open_end && n == hi);
+                    out.push(Stmt::If { cond, then, otherwise });
+                    i = n;
+                }
+                None => {
+                    let then = self.region(i + 1, m, open_end && m == hi);
+                    out.push(Stmt::If {
+                        cond,
+                        then,
+                        otherwise: vec![],
+                    });
+                    i = m;
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn render(stmts: &[Stmt], depth: usize, gotos: &BTreeSet<usize>, out: &mut String) {
+    let indent = "    ".repeat(depth);
+    for stmt in stmts {
+        match stmt {
+            Stmt::Label(addr) if gotos.contains(addr) => *out += &format!("{}label_{}:\n", indent, addr),
+            Stmt::Label(_) => {}
+            Stmt::Line(line) => *out += &format!("{}{}\n", indent, line),
+            Stmt::If { cond, then, otherwise } => {
+                *out += &format!("{}if ({}) {{\n", indent, cond);
+                render(then, depth + 1, gotos, out);
+                if !otherwise.is_empty() {
+                    *out += &format!("{}}} else {{\n", indent);
+                    render(otherwise, depth + 1, gotos, out);
+                }
+                *out += &format!("{}}}\n", indent);
+            }
+            Stmt::While { cond, body } => {
+                *out += &format!("{}while ({}) {{\n", indent, cond);
+                render(body, depth + 1, gotos, out);
+                *out += &format!("{}}}\n", indent);
+            }
+            Stmt::DoWhile { body, cond } => {
+                *out += &format!("{}do {{\n", indent);
+                render(body, depth + 1, gotos, out);
+                *out += &format!("{}}} while ({});\n", indent, cond);
+            }
+        }
+    }
+}
+
+/// Decompiles the code reachable from address 0 into a text listing.
+pub fn decompile(memory: &[i64]) -> String {
+    let control_flow = ControlFlow::recover(memory);
+
+    let mut listing = String::new();
+    for entry in &control_flow.entry_points {
+        let mut structurer = Structurer {
+            blocks: control_flow.routine(*entry),
+            loops: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+        };
+        if structurer.blocks.is_empty() {
+            continue;
+        }
+
+        // Blocks are in address order, so make sure the routine starts at its entry
+        let first = structurer.blocks.iter().position(|b| b.start == *entry).unwrap();
+        let mut body = vec![];
+        if first != 0 {
+            body.push(structurer.goto(*entry));
+        }
+        let len = structurer.blocks.len();
+        body.extend(structurer.region(0, len, false));
+
+        listing += &format!("\nfn func_{}() {{\n", entry);
+        render(&body, 1, &structurer.gotos, &mut listing);
+        listing += "}\n";
+    }
+
+    listing.trim_start().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn while_loop() {
+        let program = parse_program("3,100,1006,100,14,4,100,1001,100,-1,100,1105,1,2,99").unwrap();
+        assert_eq!(
+            decompile(&program),
+            concat!(
+                "fn func_0() {\n",
+                "    mem[100] = input();\n",
+                "    while (mem[100] != 0) {\n",
+                "        output(mem[100]);\n",
+                "        mem[100] = mem[100] - 1;\n",
+                "    }\n",
+                "    halt;\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn do_while_loop() {
+        let program = parse_program("4,13,1001,13,-1,13,1005,13,0,99,0,0,0,3").unwrap();
+        assert_eq!(
+            decompile(&program),
+            concat!(
+                "fn func_0() {\n",
+                "    do {\n",
+                "        output(mem[13]);\n",
+                "        mem[13] = mem[13] - 1;\n",
+                "    } while (mem[13] != 0);\n",
+                "    halt;\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn if_else_with_locals() {
+        let program = parse_program("109,50,203,1,21207,1,0,2,1206,2,18,21202,1,-1,1,1105,1,22,22101,0,1,3,204,1,99").unwrap();
+        assert_eq!(
+            decompile(&program),
+            concat!(
+                "fn func_0() {\n",
+                "    rb += 50;\n",
+                "    local1 = input();\n",
+                "    local2 = local1 < 0;\n",
+                "    if (local2 != 0) {\n",
+                "        local1 = local1 * -1;\n",
+                "    } else {\n",
+                "        local3 = local1;\n",
+                "    }\n",
+                "    output(local1);\n",
+                "    halt;\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn calls_and_gotos() {
+        let program = parse_program("21101,21,0,1,21101,11,0,0,1105,1,12,99,22202,1,1,1,2105,1,0").unwrap();
+        assert_eq!(
+            decompile(&program),
+            concat!(
+                "fn func_0() {\n",
+                "    local1 = 21;\n",
+                "    call func_12();\n",
+                "    halt;\n",
+                "}\n",
+                "\n",
+                "fn func_12() {\n",
+                "    local1 = local1 * local1;\n",
+                "    return;\n",
+                "}\n",
+            )
+        );
+
+        // An endless loop has no structure to recover
+        let program = parse_program("3,20,4,20,1105,1,2").unwrap();
+        assert_eq!(
+            decompile(&program),
+            concat!(
+                "fn func_0() {\n",
+                "    mem[20] = input();\n",
+                "    label_2:\n",
+                "    output(mem[20]);\n",
+                "    goto label_2;\n",
+                "}\n",
+            )
+        );
+
+        // An early return still falls through to the rest of the routine
+        let program = parse_program("203,1,2206,1,0,204,1,2105,1,0").unwrap();
+        assert_eq!(
+            decompile(&program),
+            concat!(
+                "fn func_0() {\n",
+                "    local1 = input();\n",
+                "    if (local1 == 0) return;\n",
+                "    output(local1);\n",
+                "    return;\n",
+                "}\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Hardware mapped onto a range of addresses. Addresses are passed to the device
+/// as offsets from the start of its range. Operands are read through devices,
+/// but the opcode word of an instruction is always fetched straight from memory.
+pub trait Device {
+    /// Called on every read inside the range with the value held in memory.
+    /// Returns the value the program sees.
+    fn read(&mut self, _offset: usize, stored: i64) -> i64 {
+        stored
+    }
+
+    /// Called on every write inside the range, after memory has been updated.
+    fn write(&mut self, _offset: usize, _val: i64) {}
+}
+
+/// Lets a device be attached while the caller keeps a handle to inspect it.
+impl<D: Device> Device for Rc<RefCell<D>> {
+    fn read(&mut self, offset: usize, stored: i64) -> i64 {
+        self.borrow_mut().read(offset, stored)
+    }
+
+    fn write(&mut self, offset: usize, val: i64) {
+        self.borrow_mut().write(offset, val)
+    }
+}
+
+/// A grid of pixels, one address per pixel, in rows of `width`. Writes past the
+/// last pixel, if it is attached to a larger range, are ignored.
+pub struct Framebuffer {
+    pub width: usize,
+    pub pixels: Vec<i64>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> i64 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Device for Framebuffer {
+    fn write(&mut self, offset: usize, val: i64) {
+        if let Some(pixel) = self.pixels.get_mut(offset) {
+            *pixel = val;
+        }
+    }
+}
+
+/// A register that reads as a new pseudo-random number every time. Seeded, so
+/// runs are repeatable.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed.max(1) }
+    }
+}
+
+impl Device for Random {
+    fn read(&mut self, _offset: usize, _stored: i64) -> i64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 1) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, Engine, IntcodeComputer};
+
+    struct Clock {
+        ticks: i64,
+    }
+
+    impl Device for Clock {
+        fn read(&mut self, _offset: usize, _stored: i64) -> i64 {
+            self.ticks += 1;
+            self.ticks
+        }
+    }
+
+    #[test]
+    fn clock_register() {
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&parse_program("4,100,4,100,4,100,99").unwrap());
+            computer.set_engine(*engine);
+            computer.attach_device(100..101, Clock { ticks: 0 });
+            assert_eq!(computer.run(vec![]), vec![1, 2, 3], "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn framebuffer_writes() {
+        // Copies three inputs into the framebuffer at 100..106
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&parse_program("3,100,3,104,3,105,99").unwrap());
+            computer.set_engine(*engine);
+            let framebuffer = Rc::new(RefCell::new(Framebuffer::new(3, 2)));
+            computer.attach_device(100..106, framebuffer.clone());
+            computer.run(vec![7, 8, 9]);
+
+            assert_eq!(framebuffer.borrow().pixels, vec![7, 0, 0, 0, 8, 9], "{:?}", engine);
+            assert_eq!(framebuffer.borrow().pixel(1, 1), 8);
+            assert_eq!(computer.peek(104), 8);
+        }
+    }
+
+    #[test]
+    fn framebuffer_smaller_than_its_range() {
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&parse_program("3,100,3,109,99").unwrap());
+            computer.set_engine(*engine);
+            let framebuffer = Rc::new(RefCell::new(Framebuffer::new(2, 1)));
+            computer.attach_device(100..110, framebuffer.clone());
+            computer.run(vec![7, 8]);
+
+            assert_eq!(framebuffer.borrow().pixels, vec![7, 0], "{:?}", engine);
+            assert_eq!(computer.peek(109), 8);
+        }
+    }
+
+    #[test]
+    fn opcodes_bypass_devices() {
+        // The output at 4 reads its operand at 5 through the clock, but its opcode
+        // word is fetched as stored
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&parse_program("1105,1,4,0,104,0,99").unwrap());
+            computer.set_engine(*engine);
+            computer.attach_device(4..6, Clock { ticks: 0 });
+            assert_eq!(computer.run(vec![]), vec![1], "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn random_register() {
+        let program = parse_program("4,50,4,50,99").unwrap();
+        let mut first = IntcodeComputer::new(&program);
+        first.attach_device(50..51, Random::new(42));
+        let mut second = IntcodeComputer::new(&program);
+        second.attach_device(50..51, Random::new(42));
+
+        let outputs = first.run(vec![]);
+        assert_ne!(outputs[0], outputs[1]);
+        assert_eq!(outputs, second.run(vec![]));
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operand::Position(addr) => write!(f, "[{}]", addr),
+            Operand::Immediate(val) => write!(f, "{}", val),
+            Operand::Relative(offset) if offset < 0 => write!(f, "[rb-{}]", -offset),
+            Operand::Relative(offset) => write!(f, "[rb+{}]", offset),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+}
+
+impl Op {
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Multiply => "mul",
+            Op::Input => "in",
+            Op::Output => "out",
+            Op::JumpIfTrue => "jnz",
+            Op::JumpIfFalse => "jz",
+            Op::LessThan => "lt",
+            Op::Equals => "eq",
+            Op::AdjustBase => "arb",
+            Op::Halt => "halt",
+        }
+    }
+}
+
+/// An instruction decoded from memory without running it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    pub addr: usize,
+    pub op: Op,
+    pub operands: Vec<Operand>,
+}
+
+impl Decoded {
+    /// Number of words the instruction takes up, including the opcode.
+    pub fn length(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    pub fn next_addr(&self) -> usize {
+        self.addr + self.length()
+    }
+
+    /// The value this instruction computes, if it only works on immediates.
+    fn constant_result(&self) -> Option<i64> {
+        match (self.op, self.operands[..2].as_ref()) {
+            (Op::Add, [Operand::Immediate(a), Operand::Immediate(b)]) => a.checked_add(*b),
+            (Op::Multiply, [Operand::Immediate(a), Operand::Immediate(b)]) => a.checked_mul(*b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.op.mnemonic())?;
+        for (i, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the instruction at `addr`, if the word there is a valid instruction.
+pub fn decode(memory: &[i64], addr: usize) -> Option<Decoded> {
+    let word = *memory.get(addr)?;
+    if word <= 0 {
+        return None;
+    }
+
+    let (op, arity) = match word % 100 {
+        1 => (Op::Add, 3),
+        2 => (Op::Multiply, 3),
+        3 => (Op::Input, 1),
+        4 => (Op::Output, 1),
+        5 => (Op::JumpIfTrue, 2),
+        6 => (Op::JumpIfFalse, 2),
+        7 => (Op::LessThan, 3),
+        8 => (Op::Equals, 3),
+        9 => (Op::AdjustBase, 1),
+        99 => (Op::Halt, 0),
+        _ => return None,
+    };
+
+    let operands = (0..arity)
+        .map(|i| {
+            let val = memory.get(addr + 1 + i).copied().unwrap_or(0);
+            match word / 10_i64.pow(i as u32 + 2) % 10 {
+                0 => Some(Operand::Position(val)),
+                1 => Some(Operand::Immediate(val)),
+                2 => Some(Operand::Relative(val)),
+                _ => None,
+            }
+        })
+        .collect::<Option<Vec<Operand>>>()?;
+
+    Some(Decoded { addr, op, operands })
+}
+
+/// Where control goes after an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    Next,
+    Jump(usize),
+    /// A conditional jump to `taken`, otherwise on to the next instruction.
+    Branch(usize),
+    /// A jump to a routine after storing the return address in `[rb+0]`, the
+    /// usual relative base calling convention.
+    Call { target: usize, ret: usize },
+    /// A jump to an address that is only known at run time, usually a return.
+    Indirect,
+    /// A conditional jump to an address only known at run time, otherwise on
+    /// to the next instruction.
+    IndirectBranch,
+    Halt,
+}
+
+/// Works out the control flow of `instr`. `previous` is the instruction just
+/// before it, which is needed to recognise calls.
+pub fn flow(instr: &Decoded, previous: Option<&Decoded>) -> Flow {
+    let (condition, target) = match instr.op {
+        Op::Halt => return Flow::Halt,
+        Op::JumpIfTrue | Op::JumpIfFalse => (instr.operands[0], instr.operands[1]),
+        _ => return Flow::Next,
+    };
+
+    let always = match condition {
+        Operand::Immediate(c) if (c != 0) == (instr.op == Op::JumpIfTrue) => true,
+        Operand::Immediate(_) => return Flow::Next,
+        _ => false,
+    };
+
+    let target = match target {
+        Operand::Immediate(t) if t >= 0 => t as usize,
+        _ if always => return Flow::Indirect,
+        _ => return Flow::IndirectBranch,
+    };
+
+    if !always {
+        return Flow::Branch(target);
+    }
+
+    let return_addr = previous
+        .filter(|p| p.next_addr() == instr.addr && p.operands.get(2) == Some(&Operand::Relative(0)))
+        .and_then(Decoded::constant_result);
+    match return_addr {
+        Some(ret) if ret >= 0 => Flow::Call { target, ret: ret as usize },
+        _ => Flow::Jump(target),
+    }
+}
+
+/// A run of instructions that is only entered at the top and only left at the bottom.
+#[derive(Clone, Debug)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub instructions: Vec<Decoded>,
+    pub flow: Flow,
+}
+
+impl BasicBlock {
+    pub fn end(&self) -> usize {
+        self.instructions.last().map_or(self.start, Decoded::next_addr)
+    }
+
+    /// Blocks control can go to next, not counting calls into routines.
+    pub fn successors(&self) -> Vec<usize> {
+        match self.flow {
+            Flow::Next => vec![self.end()],
+            Flow::Jump(target) => vec![target],
+            Flow::Branch(taken) => vec![taken, self.end()],
+            Flow::IndirectBranch => vec![self.end()],
+            Flow::Call { ret, .. } => vec![ret],
+            Flow::Indirect | Flow::Halt => vec![],
+        }
+    }
+}
+
+/// The code reachable from address 0, split into basic blocks.
+pub struct ControlFlow {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    /// Address 0 and the target of every call.
+    pub entry_points: BTreeSet<usize>,
+}
+
+impl ControlFlow {
+    /// Follows every jump whose target is known, starting from address 0.
+    /// Jumps through memory are not followed.
+    pub fn recover(memory: &[i64]) -> ControlFlow {
+        let mut instructions = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut entry_points = BTreeSet::new();
+        let mut pending = vec![0];
+        entry_points.insert(0);
+        leaders.insert(0);
+
+        while let Some(start) = pending.pop() {
+            let mut addr = start;
+            let mut previous: Option<Decoded> = None;
+            while !instructions.contains_key(&addr) {
+                let instr = match decode(memory, addr) {
+                    Some(instr) => instr,
+                    None => break,
+                };
+                let instr_flow = flow(&instr, previous.as_ref());
+                let next = instr.next_addr();
+                instructions.insert(addr, (instr.clone(), instr_flow));
+
+                match instr_flow {
+                    Flow::Next => {}
+                    Flow::Branch(taken) => {
+                        leaders.insert(taken);
+                        leaders.insert(next);
+                        pending.push(taken);
+                    }
+                    Flow::IndirectBranch => {
+                        leaders.insert(next);
+                    }
+                    Flow::Jump(target) => {
+                        leaders.insert(target);
+                        pending.push(target);
+                        break;
+                    }
+                    Flow::Call { target, ret } => {
+                        entry_points.insert(target);
+                        leaders.insert(target);
+                        leaders.insert(ret);
+                        pending.push(target);
+                        pending.push(ret);
+                        break;
+                    }
+                    Flow::Indirect | Flow::Halt => break,
+                }
+
+                addr = next;
+                previous = Some(instr);
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        let mut current: Option<BasicBlock> = None;
+        for (addr, (instr, instr_flow)) in instructions {
+            let continues = current
+                .as_ref()
+                .is_some_and(|b| b.flow == Flow::Next && b.end() == addr && !leaders.contains(&addr));
+            if !continues {
+                if let Some(block) = current.take() {
+                    blocks.insert(block.start, block);
+                }
+                current = Some(BasicBlock {
+                    start: addr,
+                    instructions: vec![],
+                    flow: Flow::Next,
+                });
+            }
+
+            let block = current.as_mut().unwrap();
+            block.instructions.push(instr);
+            block.flow = instr_flow;
+        }
+        if let Some(block) = current {
+            blocks.insert(block.start, block);
+        }
+
+        ControlFlow { blocks, entry_points }
+    }
+
+    /// Blocks reachable from `entry` without following calls, in address order.
+    pub fn routine(&self, entry: usize) -> Vec<&BasicBlock> {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(addr) = pending.pop() {
+            if let Some(block) = self.blocks.get(&addr) {
+                if seen.insert(addr) {
+                    pending.extend(block.successors());
+                }
+            }
+        }
+
+        seen.iter().map(|addr| &self.blocks[addr]).collect()
+    }
+}
+
+/// Lists every instruction reachable from address 0, with a label on every
+/// block that is jumped to.
+pub fn disassemble(memory: &[i64]) -> String {
+    let control_flow = ControlFlow::recover(memory);
+    let targets = control_flow
+        .blocks
+        .values()
+        .flat_map(|block| match block.flow {
+            Flow::Jump(target) | Flow::Branch(target) => vec![target],
+            Flow::Call { target, ret } => vec![target, ret],
+            _ => vec![],
+        })
+        .collect::<BTreeSet<usize>>();
+
+    let mut listing = String::new();
+    for block in control_flow.blocks.values() {
+        if control_flow.entry_points.contains(&block.start) {
+            listing += &format!("\nfunc_{}:\n", block.start);
+        } else if targets.contains(&block.start) {
+            listing += &format!("label_{}:\n", block.start);
+        }
+        for instr in &block.instructions {
+            listing += &format!("{:>6}  {}\n", instr.addr, instr);
+        }
+    }
+
+    listing.trim_start().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn decode_modes() {
+        let memory = parse_program("21101,-3,4,5,99").unwrap();
+        let instr = decode(&memory, 0).unwrap();
+        assert_eq!(instr.to_string(), "add -3, 4, [rb+5]");
+        assert_eq!(instr.length(), 4);
+        assert_eq!(decode(&memory, 4).unwrap().op, Op::Halt);
+        assert_eq!(decode(&memory, 5), None);
+        assert_eq!(decode(&parse_program("305,1,1").unwrap(), 0), None);
+    }
+
+    #[test]
+    fn basic_blocks() {
+        // Loops printing 3, 2, 1 then halts
+        let memory = parse_program("4,13,1001,13,-1,13,1005,13,0,99,0,0,0,3").unwrap();
+        let control_flow = ControlFlow::recover(&memory);
+        let starts = control_flow.blocks.keys().copied().collect::<Vec<usize>>();
+        assert_eq!(starts, vec![0, 9]);
+        assert_eq!(control_flow.blocks[&0].flow, Flow::Branch(0));
+        assert_eq!(control_flow.blocks[&9].flow, Flow::Halt);
+    }
+
+    #[test]
+    fn indirect_branches() {
+        // Returns early if the input is 0, otherwise prints it first
+        let memory = parse_program("203,1,2206,1,0,204,1,2105,1,0").unwrap();
+        let control_flow = ControlFlow::recover(&memory);
+        let starts = control_flow.blocks.keys().copied().collect::<Vec<usize>>();
+        assert_eq!(starts, vec![0, 5]);
+        assert_eq!(control_flow.blocks[&0].flow, Flow::IndirectBranch);
+        assert_eq!(control_flow.blocks[&0].successors(), vec![5]);
+        assert_eq!(control_flow.blocks[&5].flow, Flow::Indirect);
+    }
+
+    #[test]
+    fn calls() {
+        // Calls a routine at 12 that squares [rb+1], with the return address 11
+        let memory = parse_program("21101,21,0,1,21101,11,0,0,1105,1,12,99,22202,1,1,1,2105,1,0").unwrap();
+        let control_flow = ControlFlow::recover(&memory);
+        assert_eq!(control_flow.entry_points.iter().copied().collect::<Vec<usize>>(), vec![0, 12]);
+        assert_eq!(control_flow.blocks[&0].flow, Flow::Call { target: 12, ret: 11 });
+        assert_eq!(
+            disassemble(&memory),
+            concat!(
+                "func_0:\n",
+                "     0  add 21, 0, [rb+1]\n",
+                "     4  add 11, 0, [rb+0]\n",
+                "     8  jnz 1, 12\n",
+                "label_11:\n",
+                "    11  halt\n",
+                "\n",
+                "func_12:\n",
+                "    12  mul [rb+1], [rb+1], [rb+1]\n",
+                "    16  jnz 1, [rb+0]\n",
+            )
+        );
+    }
+}
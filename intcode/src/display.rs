@@ -0,0 +1,424 @@
+//! A sparse 2D screen for programs that draw by outputting `(x, y, value)`
+//! triples, rendered as text or as an RGB image through a `Palette`. A display
+//! can also keep its history as frames, to export as numbered PPM images.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub type Colour = [u8; 3];
+
+/// Renders and images are cut off this many positions across and down from
+/// the top left corner, so that a stray write far from the rest can't make
+/// them huge.
+pub const MAX_SIDE: usize = 4096;
+
+/// How each value is drawn.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    entries: HashMap<i64, (char, Colour)>,
+    blank: (char, Colour),
+}
+
+impl Palette {
+    /// A palette that draws every position, written or not, as `glyph` in
+    /// `colour`, until other values are added with `with`.
+    pub fn new(glyph: char, colour: Colour) -> Palette {
+        Palette {
+            entries: HashMap::new(),
+            blank: (glyph, colour),
+        }
+    }
+
+    pub fn with(mut self, value: i64, glyph: char, colour: Colour) -> Palette {
+        self.entries.insert(value, (glyph, colour));
+        self
+    }
+
+    /// `None` is a position that was never written.
+    pub fn glyph(&self, value: Option<i64>) -> char {
+        self.entry(value).0
+    }
+
+    pub fn colour(&self, value: Option<i64>) -> Colour {
+        self.entry(value).1
+    }
+
+    /// Changes the colours of some values, keeping their glyphs. `spec` is a
+    /// comma separated list of `value=rrggbb`, such as `0=000000,1=ffcc00`.
+    pub fn with_colours(mut self, spec: &str) -> Result<Palette, String> {
+        for entry in spec.split(',').filter(|entry| !entry.is_empty()) {
+            let parsed = entry.split_once('=').and_then(|(value, hex)| {
+                let colour = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+                Some((value.trim().parse().ok()?, colour.to_be_bytes()))
+            });
+            let (value, [_, r, g, b]) = parsed.ok_or_else(|| format!("invalid colour {:?}", entry))?;
+            let glyph = self.glyph(Some(value));
+            self.entries.insert(value, (glyph, [r, g, b]));
+        }
+        Ok(self)
+    }
+
+    fn entry(&self, value: Option<i64>) -> (char, Colour) {
+        value.and_then(|value| self.entries.get(&value)).copied().unwrap_or(self.blank)
+    }
+}
+
+/// `#` in white for 1 and black space for anything else, like most puzzle screens.
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::new(' ', [0, 0, 0]).with(1, '#', [255, 255, 255])
+    }
+}
+
+/// The smallest rectangle holding every written position, edges included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min_x: i64,
+    pub max_x: i64,
+    pub min_y: i64,
+    pub max_y: i64,
+}
+
+impl Bounds {
+    /// Just the one position.
+    pub fn point(x: i64, y: i64) -> Bounds {
+        Bounds {
+            min_x: x,
+            max_x: x,
+            min_y: y,
+            max_y: y,
+        }
+    }
+
+    /// Grown as little as needed to take in `(x, y)`.
+    pub fn including(self, x: i64, y: i64) -> Bounds {
+        Bounds {
+            min_x: self.min_x.min(x),
+            max_x: self.max_x.max(x),
+            min_y: self.min_y.min(y),
+            max_y: self.max_y.max(y),
+        }
+    }
+
+    /// Saturates at `usize::MAX`.
+    pub fn width(&self) -> usize {
+        span(self.min_x, self.max_x)
+    }
+
+    /// Saturates at `usize::MAX`.
+    pub fn height(&self) -> usize {
+        span(self.min_y, self.max_y)
+    }
+
+    /// The part that is drawn, at most `MAX_SIDE` positions each way.
+    pub fn clipped(self) -> Bounds {
+        let last = MAX_SIDE as i64 - 1;
+        Bounds {
+            max_x: self.max_x.min(self.min_x.saturating_add(last)),
+            max_y: self.max_y.min(self.min_y.saturating_add(last)),
+            ..self
+        }
+    }
+}
+
+fn span(min: i64, max: i64) -> usize {
+    usize::try_from(i128::from(max) - i128::from(min) + 1).unwrap_or(usize::MAX)
+}
+
+/// RGB pixels in rows of `width`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Colour>,
+}
+
+impl Image {
+    pub fn pixel(&self, x: usize, y: usize) -> Colour {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Blows every pixel up into a `scale` by `scale` square.
+    pub fn scaled(&self, scale: usize) -> Image {
+        let (width, height) = (self.width * scale, self.height * scale);
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x / scale, y / scale))
+            .collect();
+        Image { width, height, pixels }
+    }
+
+    /// The image as a binary PPM (P6) file.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        ppm.extend(self.pixels.iter().flatten());
+        ppm
+    }
+}
+
+/// Writes each image to `dir` as `frame_00000.ppm`, `frame_00001.ppm` and so
+/// on, creating `dir` if needed. Returns how many were written.
+pub fn save_ppm_frames(images: impl IntoIterator<Item = Image>, dir: impl AsRef<Path>) -> io::Result<usize> {
+    fs::create_dir_all(&dir)?;
+    let mut count = 0;
+    for image in images {
+        fs::write(dir.as_ref().join(format!("frame_{:05}.ppm", count)), image.to_ppm())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Display2D {
+    values: HashMap<(i64, i64), i64>,
+    bounds: Option<Bounds>,
+    palette: Palette,
+    /// The writes of every frame, the last one still open.
+    history: Option<Vec<Vec<(i64, i64, i64)>>>,
+}
+
+impl Display2D {
+    pub fn new(palette: Palette) -> Display2D {
+        Display2D {
+            values: HashMap::new(),
+            bounds: None,
+            palette,
+            history: None,
+        }
+    }
+
+    pub fn write(&mut self, x: i64, y: i64, value: i64) {
+        if let Some(frame) = self.history.as_mut().and_then(|history| history.last_mut()) {
+            frame.push((x, y, value));
+        }
+        self.values.insert((x, y), value);
+        self.bounds = Some(self.bounds.map_or(Bounds::point(x, y), |bounds| bounds.including(x, y)));
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> Option<i64> {
+        self.values.get(&(x, y)).copied()
+    }
+
+    /// Number of positions written at least once.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// `None` until something is written.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Starts keeping every write, split into frames by `end_frame`, so the
+    /// display can be played back. What it shows now becomes the first frame.
+    pub fn record_frames(&mut self) {
+        if self.history.is_none() {
+            let shown = self.iter().map(|((x, y), value)| (x, y, value)).collect();
+            self.history = Some(vec![shown, vec![]]);
+        }
+    }
+
+    /// A copy of what the display shows now, without the recorded frames.
+    pub fn without_history(&self) -> Display2D {
+        Display2D {
+            values: self.values.clone(),
+            bounds: self.bounds,
+            palette: self.palette.clone(),
+            history: None,
+        }
+    }
+
+    /// Ends the current frame. Does nothing unless recording.
+    pub fn end_frame(&mut self) {
+        if let Some(history) = self.history.as_mut() {
+            history.push(vec![]);
+        }
+    }
+
+    /// Number of frames recorded, counting the current one if it has any writes.
+    pub fn frame_count(&self) -> usize {
+        self.history.as_ref().map_or(0, |history| {
+            history.len() - history.last().map_or(0, |frame| frame.is_empty() as usize)
+        })
+    }
+
+    /// Every recorded frame as an image, all drawn within the current bounds so
+    /// they line up, with each position blown up to `scale` pixels square.
+    pub fn frame_images(&self, scale: usize) -> impl Iterator<Item = Image> + '_ {
+        let history = self.history.as_deref().unwrap_or(&[]);
+        let mut replay = Display2D::new(self.palette.clone());
+        history[..self.frame_count()].iter().map(move |writes| {
+            replay.extend(writes.iter().copied());
+            replay.image_within(self.bounds).scaled(scale)
+        })
+    }
+
+    /// Every position and its value, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), i64)> + '_ {
+        self.values.iter().map(|(position, value)| (*position, *value))
+    }
+
+    /// One line of glyphs per row, top to bottom, each ending in a newline.
+    /// Only the first `MAX_SIDE` rows and columns are drawn.
+    pub fn render(&self) -> String {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds.clipped(),
+            None => return String::new(),
+        };
+
+        let mut screen = String::new();
+        for y in bounds.min_y..=bounds.max_y {
+            for x in bounds.min_x..=bounds.max_x {
+                screen.push(self.palette.glyph(self.get(x, y)));
+            }
+            screen.push('\n');
+        }
+        screen
+    }
+
+    /// One pixel per position, with the top left corner of the bounds at (0, 0).
+    /// Only the first `MAX_SIDE` rows and columns are drawn.
+    pub fn to_image(&self) -> Image {
+        self.image_within(self.bounds)
+    }
+
+    fn image_within(&self, bounds: Option<Bounds>) -> Image {
+        let bounds = match bounds {
+            Some(bounds) => bounds.clipped(),
+            None => {
+                return Image {
+                    width: 0,
+                    height: 0,
+                    pixels: vec![],
+                }
+            }
+        };
+
+        let pixels = (bounds.min_y..=bounds.max_y)
+            .flat_map(|y| (bounds.min_x..=bounds.max_x).map(move |x| (x, y)))
+            .map(|(x, y)| self.palette.colour(self.get(x, y)))
+            .collect();
+        Image {
+            width: bounds.width(),
+            height: bounds.height(),
+            pixels,
+        }
+    }
+}
+
+impl Extend<(i64, i64, i64)> for Display2D {
+    fn extend<I: IntoIterator<Item = (i64, i64, i64)>>(&mut self, writes: I) {
+        for (x, y, value) in writes {
+            self.write(x, y, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, FrameExt, IntcodeComputer};
+
+    #[test]
+    fn tracks_bounds_and_renders() {
+        let mut display = Display2D::default();
+        assert_eq!(display.render(), "");
+        assert_eq!(display.bounds(), None);
+
+        display.extend(vec![(-1, 0, 1), (2, 1, 0), (0, 1, 1), (2, 1, 1)]);
+        assert_eq!(display.len(), 3);
+        assert_eq!(display.get(2, 1), Some(1));
+        assert_eq!(
+            display.bounds(),
+            Some(Bounds {
+                min_x: -1,
+                max_x: 2,
+                min_y: 0,
+                max_y: 1,
+            })
+        );
+        // Both edge columns are drawn
+        assert_eq!(display.render(), "#   \n # #\n");
+    }
+
+    #[test]
+    fn far_apart_writes() {
+        let mut display = Display2D::default();
+        display.extend(vec![(i64::MIN, 0, 1), (i64::MAX, 1, 1)]);
+        let bounds = display.bounds().unwrap();
+        assert_eq!((bounds.width(), bounds.height()), (usize::MAX, 2));
+
+        // Drawn from the top left corner, cut off at the right
+        let rendered = display.render();
+        assert_eq!(rendered.lines().map(|line| line.chars().count()).collect::<Vec<_>>(), vec![MAX_SIDE; 2]);
+        assert!(rendered.starts_with("# "));
+        let image = display.to_image();
+        assert_eq!((image.width, image.height), (MAX_SIDE, 2));
+    }
+
+    #[test]
+    fn palette_and_image() {
+        let palette = Palette::new('.', [0, 0, 0]).with(1, 'X', [255, 0, 0]);
+        let mut display = Display2D::new(palette);
+
+        // Draws the two ends of a diagonal
+        let program = parse_program("104,0,104,0,104,1,104,1,104,1,104,1,99").unwrap();
+        display.extend(IntcodeComputer::new(&program).outputs(vec![]).frames::<(i64, i64, i64)>());
+        assert_eq!(display.render(), "X.\n.X\n");
+
+        let image = display.to_image();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.pixel(1, 1), [255, 0, 0]);
+        assert_eq!(image.pixel(1, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn recorded_frames() {
+        let mut display = Display2D::default();
+        display.write(0, 0, 1);
+        display.record_frames();
+        display.write(1, 0, 1);
+        display.end_frame();
+        display.write(1, 1, 1);
+        assert_eq!(display.frame_count(), 3);
+
+        let images = display.frame_images(2).collect::<Vec<Image>>();
+        assert!(images.iter().all(|image| (image.width, image.height) == (4, 4)));
+        assert_eq!(images[0].pixel(3, 1), [0, 0, 0]);
+        assert_eq!(images[1].pixel(3, 1), [255, 255, 255]);
+        assert_eq!(images[1].pixel(3, 3), [0, 0, 0]);
+        assert_eq!(images[2].pixel(3, 3), [255, 255, 255]);
+
+        let copy = display.without_history();
+        assert_eq!((copy.frame_count(), copy.render()), (0, display.render()));
+
+        let ppm = images[0].to_ppm();
+        assert!(ppm.starts_with(b"P6\n4 4\n255\n"));
+        assert_eq!(ppm.len(), 11 + 4 * 4 * 3);
+    }
+
+    #[test]
+    fn palette_colours_from_spec() {
+        let palette = Palette::default().with_colours("1=ff8000,5=0000ff").unwrap();
+        assert_eq!(palette.colour(Some(1)), [255, 128, 0]);
+        assert_eq!(palette.glyph(Some(1)), '#');
+        assert_eq!(palette.colour(Some(5)), [0, 0, 255]);
+        assert!(Palette::default().with_colours("1=ff80").is_err());
+        assert!(Palette::default().with_colours("x=ff8000").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+
+/// A message sent by a program as a fixed number of consecutive outputs.
+pub trait Decoder: Sized {
+    /// Number of outputs that make up one message.
+    const ARITY: usize;
+
+    /// Decodes a frame of exactly `ARITY` outputs.
+    fn decode(frame: &[i64]) -> Self;
+}
+
+impl Decoder for (i64, i64) {
+    const ARITY: usize = 2;
+
+    fn decode(frame: &[i64]) -> (i64, i64) {
+        (frame[0], frame[1])
+    }
+}
+
+impl Decoder for (i64, i64, i64) {
+    const ARITY: usize = 3;
+
+    fn decode(frame: &[i64]) -> (i64, i64, i64) {
+        (frame[0], frame[1], frame[2])
+    }
+}
+
+/// Iterator adaptor grouping a stream of outputs into decoded messages. If the
+/// stream ends part way through a frame, the partial frame is not decoded but
+/// kept as the `remainder`.
+pub struct Frames<I, D> {
+    outputs: I,
+    buffer: Vec<i64>,
+    message: PhantomData<D>,
+}
+
+impl<I, D> Frames<I, D> {
+    /// Outputs read since the last complete message. Anything left here once
+    /// the iterator has ended is a partial frame, which usually means the
+    /// program broke the protocol.
+    pub fn remainder(&self) -> &[i64] {
+        &self.buffer
+    }
+}
+
+impl<I: Iterator<Item = i64>, D: Decoder> Iterator for Frames<I, D> {
+    type Item = D;
+
+    fn next(&mut self) -> Option<D> {
+        while self.buffer.len() < D::ARITY {
+            self.buffer.push(self.outputs.next()?);
+        }
+
+        let message = D::decode(&self.buffer);
+        self.buffer.clear();
+        Some(message)
+    }
+}
+
+pub trait FrameExt: Iterator<Item = i64> + Sized {
+    fn frames<D: Decoder>(self) -> Frames<Self, D> {
+        Frames {
+            outputs: self,
+            buffer: Vec::with_capacity(D::ARITY),
+            message: PhantomData,
+        }
+    }
+}
+
+impl<I: Iterator<Item = i64>> FrameExt for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, IntcodeComputer};
+
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Point(i64, i64),
+        Reset,
+    }
+
+    impl Decoder for Message {
+        const ARITY: usize = 2;
+
+        fn decode(frame: &[i64]) -> Message {
+            match frame {
+                [-1, -1] => Message::Reset,
+                [x, y] => Message::Point(*x, *y),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn tuples() {
+        let outputs = [1, 2, 3, 4, 5, 6, 7];
+        let mut frames = outputs.iter().copied().frames::<(i64, i64, i64)>();
+        assert_eq!(frames.by_ref().collect::<Vec<_>>(), vec![(1, 2, 3), (4, 5, 6)]);
+        assert_eq!(frames.remainder(), &[7]);
+
+        let mut frames = outputs[..6].iter().copied().frames::<(i64, i64)>();
+        assert_eq!(frames.by_ref().count(), 3);
+        assert!(frames.remainder().is_empty());
+    }
+
+    #[test]
+    fn decode_messages() {
+        let mut computer = IntcodeComputer::new(&parse_program("104,3,104,4,104,-1,104,-1,104,5,99").unwrap());
+        assert_eq!(
+            computer.outputs(vec![]).frames::<Message>().collect::<Vec<Message>>(),
+            vec![Message::Point(3, 4), Message::Reset]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::disasm;
+use crate::Snapshot;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+/// A memory address whose value differs between two snapshots.
+#[derive(Debug, PartialEq)]
+pub struct MemoryChange {
+    pub addr: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+impl fmt::Display for MemoryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>6}: {} -> {}", self.addr, self.old, self.new)
+    }
+}
+
+/// Every address whose value changed between `before` and `after`, in address order.
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Vec<MemoryChange> {
+    (0..before.memory.len().max(after.memory.len()))
+        .filter_map(|addr| {
+            let (old, new) = (before.read(addr), after.read(addr));
+            if old != new {
+                Some(MemoryChange { addr, old, new })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Renders a diff as plain text, one change per line.
+pub fn format_diff(changes: &[MemoryChange]) -> String {
+    changes.iter().map(|change| format!("{}\n", change)).collect()
+}
+
+/// Addresses below `end` that start an instruction, found by decoding
+/// instructions one after another from address 0 and skipping words that are
+/// not valid opcodes. Data mixed in with the code can make this wrong, so the
+/// current pointer is always included.
+fn instruction_starts(snapshot: &Snapshot, end: usize) -> HashSet<usize> {
+    let mut starts = HashSet::new();
+    let mut addr = 0;
+    while addr < end {
+        match disasm::decode(&snapshot.memory, addr) {
+            Some(instr) => {
+                starts.insert(addr);
+                addr += instr.length();
+            }
+            None => addr += 1,
+        }
+    }
+
+    starts.insert(snapshot.pointer);
+    starts
+}
+
+/// Renders `range` as a table of `columns` words per row. Each word is prefixed
+/// with `>` if it is at the pointer, `|` if it starts an instruction, or a space.
+/// Panics if `columns` is 0.
+pub fn dump_memory(snapshot: &Snapshot, range: Range<usize>, columns: usize) -> String {
+    assert!(columns > 0, "A memory dump needs at least one column");
+    let starts = instruction_starts(snapshot, range.end);
+    let width = range
+        .clone()
+        .map(|addr| snapshot.read(addr).to_string().len())
+        .chain(std::iter::once(format!("+{}", columns - 1).len()))
+        .max()
+        .unwrap();
+    let addr_width = range.end.saturating_sub(1).to_string().len().max(4);
+
+    let mut dump = format!("{:>w$}", "addr", w = addr_width);
+    for column in 0..columns {
+        dump += &format!("  {:>w$}", format!("+{}", column), w = width);
+    }
+    dump += "\n";
+
+    for row_start in range.clone().step_by(columns) {
+        dump += &format!("{:>w$}", row_start, w = addr_width);
+        for addr in row_start..(row_start + columns).min(range.end) {
+            let marker = if addr == snapshot.pointer {
+                '>'
+            } else if starts.contains(&addr) {
+                '|'
+            } else {
+                ' '
+            };
+            dump += &format!(" {}{:>w$}", marker, snapshot.read(addr), w = width);
+        }
+        dump += "\n";
+    }
+
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, IntcodeComputer};
+
+    #[test]
+    fn diff_after_run() {
+        let mut computer = IntcodeComputer::new(&parse_program("1002,4,3,4,33").unwrap());
+        let before = computer.snapshot();
+        computer.run(vec![]);
+
+        let changes = diff(&before, &computer.snapshot());
+        assert_eq!(changes, vec![MemoryChange { addr: 4, old: 33, new: 99 }]);
+        assert_eq!(format_diff(&changes), "     4: 33 -> 99\n");
+    }
+
+    #[test]
+    fn dump_with_markers() {
+        let mut computer = IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8").unwrap());
+        computer.queue_input(8);
+        computer.step();
+        assert_eq!(
+            dump_memory(&computer.snapshot(), 0..11, 4),
+            concat!(
+                "addr  +0  +1  +2  +3\n",
+                "   0 | 3   9 > 8   9\n",
+                "   4  10   9 | 4   9\n",
+                "   8 |99 | 8   8\n",
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one column")]
+    fn dump_without_columns() {
+        let computer = IntcodeComputer::new(&parse_program("99").unwrap());
+        dump_memory(&computer.snapshot(), 0..1, 0);
+    }
+}
@@ -1,3 +1,37 @@
+pub mod binary;
+mod compiled;
+pub mod dap;
+pub mod decompile;
+pub mod device;
+pub mod disasm;
+pub mod display;
+pub mod framing;
+pub mod inspect;
+pub mod program;
+pub mod recording;
+pub mod scheduler;
+pub mod search;
+pub mod snapshot;
+pub mod symbolic;
+pub mod tui;
+
+pub use compiled::Engine;
+pub use device::Device;
+pub use display::{save_ppm_frames, Display2D, Palette};
+pub use framing::{Decoder, FrameExt};
+pub use program::{load_program, parse_program, program_from_args, program_or_default, read_program, ParseError};
+pub use recording::Recording;
+pub use scheduler::{Policy, Scheduler};
+pub use search::{Outcome, Search};
+pub use snapshot::Snapshot;
+pub use symbolic::{solve_linear, Expr, Symbolic};
+
+use recording::Event;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
 #[derive(Debug)]
 enum Mode {
     Position,
@@ -5,12 +39,26 @@ enum Mode {
     Relative,
 }
 
-#[derive(Debug)]
-enum Type {
+/// How an instruction parameter is read: as a value, or as the address to write to.
+#[derive(Debug, Clone, Copy)]
+pub enum Type {
     Parameter,
     Address,
 }
 
+struct ParamModes(Vec<u8>);
+
+impl ParamModes {
+    fn pop(&mut self) -> Mode {
+        match self.0.pop().unwrap_or(0) {
+            0 => Mode::Position,
+            1 => Mode::Immediate,
+            2 => Mode::Relative,
+            _ => panic!("Unknown parameter mode."),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum Instruction {
     Add(i64, i64, usize),
@@ -22,6 +70,7 @@ enum Instruction {
     LessThan(i64, i64, usize),
     Equals(i64, i64, usize),
     RelativeBaseOffset(i64),
+    Extension(u8, Vec<i64>),
     Halt,
 }
 
@@ -31,24 +80,90 @@ pub enum Output {
     Exit
 }
 
+/// Handler for a registered opcode. Receives the decoded parameters, and returns a
+/// value to output, if any.
+pub type OpcodeHandler = Box<dyn FnMut(&mut IntcodeComputer, &[i64]) -> Option<i64>>;
+
+struct Extension {
+    params: Vec<Type>,
+    handler: OpcodeHandler,
+}
+
+/// A write into memory that had already been executed as part of an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfModification {
+    /// Address of the instruction that made the write.
+    pub writer: usize,
+    pub addr: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+impl std::fmt::Display for SelfModification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "instruction at {} overwrote {} with {} (was {})",
+            self.writer, self.addr, self.new, self.old
+        )
+    }
+}
+
+#[derive(Default)]
+struct SelfModificationTracker {
+    executed: Vec<bool>,
+    modifications: Vec<SelfModification>,
+}
+
+impl SelfModificationTracker {
+    fn mark_executed(&mut self, addr: usize) {
+        if addr >= self.executed.len() {
+            self.executed.resize(addr + 1, false);
+        }
+        self.executed[addr] = true;
+    }
+
+    fn was_executed(&self, addr: usize) -> bool {
+        self.executed.get(addr).copied().unwrap_or(false)
+    }
+}
+
 pub struct IntcodeComputer {
     memory: Vec<i64>,
     pointer: usize,
     relative_base: i64,
-    input_stack: Vec<i64>,
+    instructions_executed: u64,
+    memory_limit: usize,
+    inputs: VecDeque<i64>,
     wait_for_input_addr: Option<usize>,
-    has_exited: bool
+    has_exited: bool,
+    extensions: HashMap<u8, Extension>,
+    devices: Vec<(Range<usize>, Box<dyn Device>)>,
+    instruction_start: usize,
+    self_modification: Option<SelfModificationTracker>,
+    recording: Option<Recording>,
+    engine: Engine,
+    compiled: compiled::Cache,
 }
 
 impl IntcodeComputer {
-    pub fn new(program: &Vec<i64>) -> IntcodeComputer {
+    pub fn new(program: &[i64]) -> IntcodeComputer {
         IntcodeComputer {
-            memory: program.clone(),
+            memory: program.to_vec(),
             pointer: 0,
             relative_base: 0,
-            input_stack: vec!(),
+            instructions_executed: 0,
+            memory_limit: usize::MAX,
+            inputs: VecDeque::new(),
             wait_for_input_addr: None,
-            has_exited: false
+            has_exited: false,
+            extensions: HashMap::new(),
+            devices: vec![],
+            instruction_start: 0,
+            self_modification: None,
+            recording: None,
+            engine: Engine::default(),
+            compiled: compiled::Cache::default(),
         }
     }
 
@@ -56,45 +171,209 @@ impl IntcodeComputer {
         self.has_exited
     }
 
+    /// Number of instructions executed so far.
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
+
+    /// Reads memory without growing it; addresses past the end read as 0.
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.memory.get(addr).copied().unwrap_or(0)
+    }
+
+    /// Writes memory without notifying any attached device.
+    pub fn poke(&mut self, addr: usize, val: i64) {
+        self.check_for_resize(addr);
+        self.compiled.invalidate(addr);
+        self.memory[addr] = val;
+    }
+
+    /// Takes the next value from the inputs the computer was given, if any remain.
+    pub fn next_input(&mut self) -> Option<i64> {
+        self.inputs.pop_front()
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Switches how instructions are executed from now on. Either engine gives
+    /// the same results.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.compiled.clear();
+        if engine == Engine::Compiled {
+            self.precompile();
+        }
+    }
+
+    /// Caps how far memory may grow. Accessing an address past the limit panics.
+    pub fn set_memory_limit(&mut self, words: usize) {
+        self.memory_limit = words;
+    }
+
+    /// Starts recording which addresses are executed as part of an instruction,
+    /// and flags any later write to one of them.
+    pub fn track_self_modification(&mut self) {
+        self.self_modification.get_or_insert_with(SelfModificationTracker::default);
+    }
+
+    /// Writes into already executed code, in the order they happened. Empty
+    /// unless tracking was switched on.
+    pub fn self_modifications(&self) -> &[SelfModification] {
+        self.self_modification
+            .as_ref()
+            .map_or(&[], |tracker| &tracker.modifications)
+    }
+
+    /// Starts recording every value read by an input instruction and every
+    /// output, with the instruction count at the time.
+    pub fn record_io(&mut self) {
+        self.recording.get_or_insert_with(Recording::default);
+    }
+
+    /// The I/O recorded so far, if recording was switched on.
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    fn record(&mut self, event: Event) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.events.push(event);
+        }
+    }
+
+    fn record_input(&mut self, value: i64) {
+        let at = self.instructions_executed;
+        self.record(Event::Input { at, value });
+    }
+
+    fn record_output(&mut self, value: i64) {
+        let at = self.instructions_executed;
+        self.record(Event::Output { at, value });
+    }
+
+    /// Maps a device onto a range of addresses. Every read and write the program
+    /// makes inside the range, including operand fetches, goes through the device.
+    /// Opcode words are the exception, and are always read from memory.
+    pub fn attach_device<D: Device + 'static>(&mut self, range: Range<usize>, device: D) {
+        self.devices.push((range, Box::new(device)));
+        self.compiled.clear();
+    }
+
+    /// Adds an opcode to the instruction set. Its parameters are decoded using the
+    /// usual parameter modes and passed to `handler` when the instruction runs.
+    pub fn register_opcode<F>(&mut self, opcode: u8, params: &[Type], handler: F)
+    where
+        F: FnMut(&mut IntcodeComputer, &[i64]) -> Option<i64> + 'static,
+    {
+        if opcode <= 9 || opcode == 99 {
+            panic!("Opcode {} is built in", opcode);
+        }
+        if opcode > 99 {
+            panic!("Opcodes must be two digits");
+        }
+
+        self.extensions.insert(
+            opcode,
+            Extension {
+                params: params.to_vec(),
+                handler: Box::new(handler),
+            },
+        );
+    }
+
+    pub fn waiting_for_input(&self) -> bool {
+        self.wait_for_input_addr.is_some()
+    }
+
     pub fn provide_input(&mut self, input: i64) {
-        self.write(self.wait_for_input_addr.expect("Tried to write input when it was not expected!"), input);
+        let addr = self.wait_for_input_addr.take().expect("Tried to write input when it was not expected!");
+        self.record_input(input);
+        self.write(addr, input);
+    }
+
+    /// Adds a value to the back of the input queue.
+    pub fn queue_input(&mut self, input: i64) {
+        self.inputs.push_back(input);
     }
 
     fn check_for_resize(&mut self, addr: usize) {
         if addr >= self.memory.len() {
+            if addr >= self.memory_limit {
+                panic!("Address {} is past the memory limit", addr);
+            }
             self.memory.resize(addr + 1, 0);
         }
     }
 
     fn read(&mut self, addr: usize) -> i64 {
         self.check_for_resize(addr);
-        self.memory[addr]
+        let mut val = self.memory[addr];
+        for (range, device) in self.devices.iter_mut() {
+            if range.contains(&addr) {
+                val = device.read(addr - range.start, val);
+            }
+        }
+
+        val
     }
 
     fn write(&mut self, addr: usize, val: i64) {
         self.check_for_resize(addr);
+        if let Some(tracker) = self.self_modification.as_mut() {
+            if tracker.was_executed(addr) {
+                tracker.modifications.push(SelfModification {
+                    writer: self.instruction_start,
+                    addr,
+                    old: self.memory[addr],
+                    new: val,
+                });
+            }
+        }
+        self.compiled.invalidate(addr);
         self.memory[addr] = val;
+        for (range, device) in self.devices.iter_mut() {
+            if range.contains(&addr) {
+                device.write(addr - range.start, val);
+            }
+        }
     }
 
     fn read_next_parameter(&mut self, param_type: Type, param_mode: Mode) -> i64 {
         let address = match param_mode {
-            Mode::Position => self.read(self.pointer) as usize,
-            Mode::Immediate => self.pointer,
-            Mode::Relative => (self.relative_base + self.read(self.pointer)) as usize,
+            Mode::Position => self.read(self.pointer),
+            Mode::Immediate => self.pointer as i64,
+            Mode::Relative => self.relative_base + self.read(self.pointer),
         };
+        if address < 0 {
+            panic!("Negative address {}", address);
+        }
+        let address = address as usize;
 
         let read_param = match param_type {
             Type::Parameter => self.read(address),
             Type::Address => address as i64,
         };
 
+        if let Some(tracker) = self.self_modification.as_mut() {
+            tracker.mark_executed(self.pointer);
+        }
         self.pointer += 1;
 
-        return read_param;
+        read_param
     }
 
     fn split_instruction(&mut self) -> Vec<u8> {
         let instr = self.memory[self.pointer];
+        self.instruction_start = self.pointer;
+        if let Some(tracker) = self.self_modification.as_mut() {
+            tracker.mark_executed(self.pointer);
+        }
         self.pointer += 1;
 
         if instr == 0 {
@@ -115,41 +394,33 @@ impl IntcodeComputer {
 
     fn next_instruction(&mut self) -> Instruction {
         let instruction = self.split_instruction();
+        self.instructions_executed += 1;
 
-        //check for halt
-        if instruction.len() > 1 && instruction[0] == 9 && instruction[1] == 9 {
+        let opcode = instruction[0] + 10 * instruction.get(1).copied().unwrap_or(0);
+        if opcode == 99 {
             return Instruction::Halt;
         }
 
-        let opcode = instruction[0];
-
-        let mut param_stack: Vec<Mode> = if instruction.len() > 2 {
-            instruction[2..]
-                .iter()
-                .rev()
-                .map(|p| match p {
-                    0 => Mode::Position,
-                    1 => Mode::Immediate,
-                    2 => Mode::Relative,
-                    _ => panic!("Unknown parameter mode."),
-                })
-                .collect()
+        // Modes are only decoded for the parameters the instruction has, so any
+        // digits beyond those are ignored.
+        let mut param_stack = ParamModes(if instruction.len() > 2 {
+            instruction[2..].iter().rev().copied().collect()
         } else {
             vec![]
-        };
+        });
 
         match opcode {
             1 | 2 | 7 | 8 => {
                 let p1 = self.read_next_parameter(
                     Type::Parameter,
-                    param_stack.pop().unwrap_or(Mode::Position),
+                    param_stack.pop(),
                 );
                 let p2 = self.read_next_parameter(
                     Type::Parameter,
-                    param_stack.pop().unwrap_or(Mode::Position),
+                    param_stack.pop(),
                 );
                 let addr = self
-                    .read_next_parameter(Type::Address, param_stack.pop().unwrap_or(Mode::Position))
+                    .read_next_parameter(Type::Address, param_stack.pop())
                     as usize;
 
                 if opcode == 1 {
@@ -163,23 +434,23 @@ impl IntcodeComputer {
                 }
             }
             3 => Instruction::Input(
-                self.read_next_parameter(Type::Address, param_stack.pop().unwrap_or(Mode::Position))
+                self.read_next_parameter(Type::Address, param_stack.pop())
                     as usize,
             ),
             4 => {
                 Instruction::Output(self.read_next_parameter(
                     Type::Parameter,
-                    param_stack.pop().unwrap_or(Mode::Position),
+                    param_stack.pop(),
                 ))
             }
             5 | 6 => {
                 let p1 = self.read_next_parameter(
                     Type::Parameter,
-                    param_stack.pop().unwrap_or(Mode::Position),
+                    param_stack.pop(),
                 );
                 let p2 = self.read_next_parameter(
                     Type::Parameter,
-                    param_stack.pop().unwrap_or(Mode::Position),
+                    param_stack.pop(),
                 );
                 if opcode == 5 {
                     Instruction::JumpIfTrue(p1, p2)
@@ -190,51 +461,127 @@ impl IntcodeComputer {
             9 => {
                 Instruction::RelativeBaseOffset(self.read_next_parameter(
                     Type::Parameter,
-                    param_stack.pop().unwrap_or(Mode::Position),
+                    param_stack.pop(),
                 ))
             }
-            _ => panic!("Unknown instruction opcode"),
+            _ => {
+                let params = match self.extensions.get(&opcode) {
+                    Some(extension) => extension.params.clone(),
+                    None => panic!("Unknown instruction opcode"),
+                };
+
+                Instruction::Extension(
+                    opcode,
+                    params
+                        .into_iter()
+                        .map(|param_type| {
+                            self.read_next_parameter(
+                                param_type,
+                                param_stack.pop(),
+                            )
+                        })
+                        .collect(),
+                )
+            }
         }
     }
 
-    fn internal_run(&mut self) -> Output {
-        loop {
-            match self.next_instruction() {
-                Instruction::Add(p1, p2, addr) => self.write(addr, p1 + p2),
-                Instruction::Multiply(p1, p2, addr) => self.write(addr, p1 * p2),
-                Instruction::Input(addr) => {
-                    if let Some(next_input) = self.input_stack.pop() {
-                        self.write(addr, next_input);
-                    } else {
-                        self.wait_for_input_addr = Some(addr);
-                        return Output::WaitingForInput;
-                    }
-                },
-                Instruction::Output(p) => return Output::OutputVal(p),
-                Instruction::JumpIfTrue(p1, p2) => {
-                    self.pointer = if p1 != 0 { p2 as usize } else { self.pointer }
-                },
-                Instruction::JumpIfFalse(p1, p2) => {
-                    self.pointer = if p1 == 0 { p2 as usize } else { self.pointer }
-                },
-                Instruction::LessThan(p1, p2, addr) => {
-                    self.write(addr, if p1 < p2 { 1 } else { 0 })
-                },
-                Instruction::Equals(p1, p2, addr) => self.write(addr, if p1 == p2 { 1 } else { 0 }),
-                Instruction::RelativeBaseOffset(p1) => self.relative_base += p1,
-                Instruction::Halt => {
-                    break;
+    /// Executes a single instruction, returning anything the caller needs to act on.
+    ///
+    /// Mode digits are only read for the parameters an instruction has, so any
+    /// beyond those are ignored. A faulty instruction panics: an unknown opcode,
+    /// an unknown mode for one of its parameters, a negative address, or an
+    /// addition or multiplication that overflows.
+    pub fn step(&mut self) -> Option<Output> {
+        self.step_up_to(1)
+    }
+
+    /// Executes up to `limit` instructions, stopping early on anything the caller
+    /// needs to act on. The interpreter always executes one.
+    fn step_up_to(&mut self, limit: usize) -> Option<Output> {
+        if self.has_exited {
+            return Some(Output::Exit);
+        }
+
+        if let Some(addr) = self.wait_for_input_addr {
+            match self.inputs.pop_front() {
+                Some(next_input) => {
+                    self.wait_for_input_addr = None;
+                    self.record_input(next_input);
+                    self.write(addr, next_input);
+                    return None;
                 }
-            };
+                None => return Some(Output::WaitingForInput),
+            }
         }
 
-        self.has_exited = true;
-        Output::Exit
+        if self.engine == Engine::Compiled {
+            if let Some(output) = self.run_compiled(limit) {
+                return output;
+            }
+        }
+
+        match self.next_instruction() {
+            Instruction::Add(p1, p2, addr) => {
+                self.write(addr, p1.checked_add(p2).expect("Arithmetic overflow"))
+            },
+            Instruction::Multiply(p1, p2, addr) => {
+                self.write(addr, p1.checked_mul(p2).expect("Arithmetic overflow"))
+            },
+            Instruction::Input(addr) => {
+                if let Some(next_input) = self.inputs.pop_front() {
+                    self.record_input(next_input);
+                    self.write(addr, next_input);
+                } else {
+                    self.wait_for_input_addr = Some(addr);
+                    return Some(Output::WaitingForInput);
+                }
+            },
+            Instruction::Output(p) => {
+                self.record_output(p);
+                return Some(Output::OutputVal(p));
+            },
+            Instruction::JumpIfTrue(p1, p2) => {
+                self.pointer = if p1 != 0 { p2 as usize } else { self.pointer }
+            },
+            Instruction::JumpIfFalse(p1, p2) => {
+                self.pointer = if p1 == 0 { p2 as usize } else { self.pointer }
+            },
+            Instruction::LessThan(p1, p2, addr) => {
+                self.write(addr, if p1 < p2 { 1 } else { 0 })
+            },
+            Instruction::Equals(p1, p2, addr) => self.write(addr, if p1 == p2 { 1 } else { 0 }),
+            Instruction::RelativeBaseOffset(p1) => self.relative_base += p1,
+            Instruction::Extension(opcode, params) => {
+                let mut extension = self.extensions.remove(&opcode).unwrap();
+                let output = (extension.handler)(self, &params);
+                self.extensions.insert(opcode, extension);
+
+                if let Some(val) = output {
+                    self.record_output(val);
+                    return Some(Output::OutputVal(val));
+                }
+            },
+            Instruction::Halt => {
+                self.has_exited = true;
+                return Some(Output::Exit);
+            }
+        };
+
+        None
+    }
+
+    fn internal_run(&mut self) -> Output {
+        loop {
+            if let Some(output) = self.step_up_to(usize::MAX) {
+                return output;
+            }
+        }
     }
 
     pub fn run(&mut self, input: Vec<i64>) -> Vec<i64> {
         let mut all_outputs = vec![];
-        self.input_stack = input.into_iter().rev().collect::<Vec<i64>>();
+        self.inputs = input.into_iter().collect::<VecDeque<i64>>();
         loop {
             match self.internal_run() {
                 Output::OutputVal(x) => all_outputs.push(x),
@@ -249,13 +596,126 @@ impl IntcodeComputer {
     pub fn tick(&mut self) -> Output {
         self.internal_run()
     }
+
+    /// Like `tick`, but gives up and returns `None` once `limit` more
+    /// instructions have run without anything for the caller to act on.
+    pub fn tick_within(&mut self, limit: u64) -> Option<Output> {
+        let deadline = self.instructions_executed.saturating_add(limit);
+        while self.instructions_executed < deadline {
+            let remaining = usize::try_from(deadline - self.instructions_executed).unwrap_or(usize::MAX);
+            if let Some(output) = self.step_up_to(remaining) {
+                return Some(output);
+            }
+        }
+        None
+    }
+
+    /// Queues `inputs` and returns an iterator that runs the program lazily,
+    /// yielding each output as it is produced.
+    pub fn outputs(&mut self, inputs: Vec<i64>) -> Outputs<'_> {
+        for input in inputs {
+            self.queue_input(input);
+        }
+
+        Outputs { computer: self }
+    }
+
+    /// Runs until `n` more outputs have been produced, or the program halts or
+    /// runs out of input first.
+    pub fn take_outputs(&mut self, n: usize) -> Vec<i64> {
+        self.outputs(vec![]).take(n).collect()
+    }
+
+    /// Calls the routine at `address` using the relative base calling convention:
+    /// the return address is stored at `[relative_base]` and the arguments from
+    /// `[relative_base + 1]` onwards. Runs until the routine jumps back to
+    /// `return_address`, then returns the value at `[relative_base + result_slot]`.
+    /// Any outputs produced by the routine are discarded.
+    ///
+    /// Gives up after `max_steps` instructions. If the call fails, the computer
+    /// is left wherever the routine stopped.
+    pub fn call(
+        &mut self,
+        address: usize,
+        args: &[i64],
+        return_address: usize,
+        result_slot: i64,
+        max_steps: u64,
+    ) -> Result<i64, CallError> {
+        if self.relative_base < 0 {
+            return Err(CallError::NegativeAddress(self.relative_base));
+        }
+        let saved_pointer = self.pointer;
+        let frame = self.relative_base as usize;
+
+        self.write(frame, return_address as i64);
+        for (i, arg) in args.iter().enumerate() {
+            self.write(frame + 1 + i, *arg);
+        }
+
+        self.pointer = address;
+        let deadline = self.instructions_executed.saturating_add(max_steps);
+        while self.pointer != return_address {
+            if self.instructions_executed >= deadline {
+                return Err(CallError::StepLimit);
+            }
+            match self.step() {
+                Some(Output::WaitingForInput) => return Err(CallError::WaitingForInput),
+                Some(Output::Exit) => return Err(CallError::Halted),
+                _ => {}
+            }
+        }
+        self.pointer = saved_pointer;
+
+        let result = self.relative_base + result_slot;
+        if result < 0 {
+            return Err(CallError::NegativeAddress(result));
+        }
+        Ok(self.read(result as usize))
+    }
 }
 
-pub fn parse_program(program: &'static str) -> Vec<i64> {
-    program
-        .split(',')
-        .map(|n| n.parse::<i64>().unwrap())
-        .collect::<Vec<i64>>()
+/// Why an `IntcodeComputer::call` did not return a result.
+#[derive(Debug, PartialEq)]
+pub enum CallError {
+    /// The stack frame or the result slot would be at this negative address.
+    NegativeAddress(i64),
+    /// The routine asked for input.
+    WaitingForInput,
+    /// The program halted before the routine returned.
+    Halted,
+    /// The routine ran for longer than allowed without returning.
+    StepLimit,
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            CallError::WaitingForInput => write!(f, "routine asked for input"),
+            CallError::Halted => write!(f, "program halted before returning from call"),
+            CallError::StepLimit => write!(f, "routine did not return within the step limit"),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+/// Iterator over a computer's outputs. Ends when the program halts, or when it
+/// needs input that has not been queued; check `waiting_for_input` to tell which.
+pub struct Outputs<'a> {
+    computer: &'a mut IntcodeComputer,
+}
+
+impl Iterator for Outputs<'_> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        match self.computer.internal_run() {
+            Output::OutputVal(x) => Some(x),
+            Output::WaitingForInput | Output::Exit => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -265,7 +725,7 @@ mod tests {
     #[test]
     fn input_output() {
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,0,4,0,99"))
+            *IntcodeComputer::new(&parse_program("3,0,4,0,99").unwrap())
                 .run(vec!(1))
                 .last()
                 .unwrap(),
@@ -276,28 +736,28 @@ mod tests {
     #[test]
     fn equal_to() {
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8"))
+            *IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8").unwrap())
                 .run(vec!(8))
                 .last()
                 .unwrap(),
             1
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8"))
+            *IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8").unwrap())
                 .run(vec!(10))
                 .last()
                 .unwrap(),
             0
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,3,1108,-1,8,3,4,3,99"))
+            *IntcodeComputer::new(&parse_program("3,3,1108,-1,8,3,4,3,99").unwrap())
                 .run(vec!(8))
                 .last()
                 .unwrap(),
             1
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,3,1108,-1,8,3,4,3,99"))
+            *IntcodeComputer::new(&parse_program("3,3,1108,-1,8,3,4,3,99").unwrap())
                 .run(vec!(10))
                 .last()
                 .unwrap(),
@@ -308,28 +768,28 @@ mod tests {
     #[test]
     fn less_than() {
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,9,7,9,10,9,4,9,99,-1,8"))
+            *IntcodeComputer::new(&parse_program("3,9,7,9,10,9,4,9,99,-1,8").unwrap())
                 .run(vec!(7))
                 .last()
                 .unwrap(),
             1
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8"))
+            *IntcodeComputer::new(&parse_program("3,9,8,9,10,9,4,9,99,-1,8").unwrap())
                 .run(vec!(9))
                 .last()
                 .unwrap(),
             0
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,3,1107,-1,8,3,4,3,99"))
+            *IntcodeComputer::new(&parse_program("3,3,1107,-1,8,3,4,3,99").unwrap())
                 .run(vec!(7))
                 .last()
                 .unwrap(),
             1
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,3,1107,-1,8,3,4,3,99"))
+            *IntcodeComputer::new(&parse_program("3,3,1107,-1,8,3,4,3,99").unwrap())
                 .run(vec!(9))
                 .last()
                 .unwrap(),
@@ -340,28 +800,28 @@ mod tests {
     #[test]
     fn compare_to_zero() {
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9"))
+            *IntcodeComputer::new(&parse_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9").unwrap())
                 .run(vec!(0))
                 .last()
                 .unwrap(),
             0
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9"))
+            *IntcodeComputer::new(&parse_program("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9").unwrap())
                 .run(vec!(-1))
                 .last()
                 .unwrap(),
             1
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1"))
+            *IntcodeComputer::new(&parse_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap())
                 .run(vec!(0))
                 .last()
                 .unwrap(),
             0
         );
         assert_eq!(
-            *IntcodeComputer::new(&parse_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1"))
+            *IntcodeComputer::new(&parse_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap())
                 .run(vec!(-1))
                 .last()
                 .unwrap(),
@@ -370,7 +830,7 @@ mod tests {
     }
     #[test]
     fn compare_to_val() {
-        let program = parse_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99");
+        let program = parse_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap();
         assert_eq!(*IntcodeComputer::new(&program).run(vec!(0)).last().unwrap(), 999);
         assert_eq!(*IntcodeComputer::new(&program).run(vec!(8)).last().unwrap(), 1000);
         assert_eq!(*IntcodeComputer::new(&program).run(vec!(20)).last().unwrap(), 1001);
@@ -378,13 +838,13 @@ mod tests {
 
     #[test]
     fn quine_test() {
-        let prog = parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+        let prog = parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
         assert_eq!(IntcodeComputer::new(&prog).run(vec!()), prog);
     }
     #[test]
     fn sixteen_digit_output() {
         assert_eq!(
-            IntcodeComputer::new(&parse_program("1102,34915192,34915192,7,4,7,99,0"))
+            IntcodeComputer::new(&parse_program("1102,34915192,34915192,7,4,7,99,0").unwrap())
                 .run(vec![])
                 .last()
                 .unwrap()
@@ -393,9 +853,155 @@ mod tests {
             16
         );
     }
+
+    #[test]
+    fn call_subroutine() {
+        // add(a, b): stores a + b in the first argument slot and returns
+        let prog = parse_program("0,0,0,109,3,22201,-2,-1,-2,109,-3,2106,0,0").unwrap();
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&prog);
+            computer.set_engine(*engine);
+            assert_eq!(computer.call(3, &[2, 5], 1000, 1, 100), Ok(7), "{:?}", engine);
+            assert_eq!(computer.call(3, &[-4, 1], 1000, 1, 100), Ok(-3), "{:?}", engine);
+            assert_eq!(computer.call(3, &[2, 5], 1000, 1, 2), Err(CallError::StepLimit), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn call_errors() {
+        // Moves the frame out of the way, then routines that loop forever, ask
+        // for input, and halt
+        let prog = parse_program("109,20,1105,1,2,3,0,99").unwrap();
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            for (routine, error) in [(2, CallError::StepLimit), (5, CallError::WaitingForInput), (7, CallError::Halted)] {
+                let mut computer = IntcodeComputer::new(&prog);
+                computer.set_engine(*engine);
+                computer.step();
+                assert_eq!(computer.call(routine, &[], 1000, 0, 50), Err(error), "{:?}", engine);
+            }
+
+            let mut computer = IntcodeComputer::new(&parse_program("109,-5,99").unwrap());
+            computer.set_engine(*engine);
+            computer.step();
+            assert_eq!(computer.call(2, &[], 1000, 0, 50), Err(CallError::NegativeAddress(-5)), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn custom_opcodes() {
+        // 20: square p1 into p2, 30: output p1 + the next input
+        let prog = parse_program("120,5,11,4,11,30,11,130,-3,99,0,0").unwrap();
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&prog);
+            computer.set_engine(*engine);
+            computer.register_opcode(20, &[Type::Parameter, Type::Address], |c, params| {
+                c.poke(params[1] as usize, params[0] * params[0]);
+                None
+            });
+            computer.register_opcode(30, &[Type::Parameter], |c, params| {
+                Some(params[0] + c.next_input().unwrap())
+            });
+            assert_eq!(computer.run(vec![1, 2]), vec![25, 26, -1], "{:?}", engine);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown instruction opcode")]
+    fn unregistered_opcode() {
+        IntcodeComputer::new(&parse_program("1020,5,11,99").unwrap()).run(vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown instruction opcode")]
+    fn unregistered_opcode_compiled() {
+        let mut computer = IntcodeComputer::new(&parse_program("1101,1,1,9,1020,5,11,99").unwrap());
+        computer.set_engine(Engine::Compiled);
+        computer.run(vec![]);
+    }
+
+    #[test]
+    fn extra_mode_digits() {
+        // Jump-if-true only has two parameters, so the 7 and 8 are never read
+        let prog = parse_program("781105,1,4,99,104,7,99").unwrap();
+        assert_eq!(IntcodeComputer::new(&prog).run(vec![]), vec![7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown parameter mode.")]
+    fn unknown_parameter_mode() {
+        IntcodeComputer::new(&parse_program("301,0,0,0,99").unwrap()).run(vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Negative address -1")]
+    fn negative_address() {
+        IntcodeComputer::new(&parse_program("4,-1,99").unwrap()).run(vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Arithmetic overflow")]
+    fn arithmetic_overflow() {
+        let prog = parse_program(&format!("1102,{},2,0,99", i64::MAX)).unwrap();
+        IntcodeComputer::new(&prog).run(vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Address 100 is past the memory limit")]
+    fn memory_limit() {
+        let mut computer = IntcodeComputer::new(&parse_program("1101,1,1,100,99").unwrap());
+        computer.set_memory_limit(50);
+        computer.run(vec![]);
+    }
+
+    #[test]
+    fn streaming_outputs() {
+        // Echoes inputs until it reads a 0
+        let prog = parse_program("3,11,1006,11,10,4,11,1105,1,0,99,0").unwrap();
+        let mut computer = IntcodeComputer::new(&prog);
+
+        let mut outputs = computer.outputs(vec![4, 5]);
+        assert_eq!(outputs.next(), Some(4));
+        assert_eq!(outputs.next(), Some(5));
+        assert_eq!(outputs.next(), None);
+        assert!(computer.waiting_for_input());
+
+        computer.queue_input(6);
+        computer.queue_input(7);
+        assert_eq!(computer.take_outputs(1), vec![6]);
+        assert_eq!(computer.outputs(vec![0]).collect::<Vec<i64>>(), vec![7]);
+        assert!(computer.finished());
+        assert_eq!(computer.take_outputs(1), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn self_modification() {
+        // Increments its own first operand, then writes to data
+        for engine in &[Engine::Interpreter, Engine::Compiled] {
+            let mut computer = IntcodeComputer::new(&parse_program("1001,1,1,1,1101,2,3,9,99,0").unwrap());
+            computer.set_engine(*engine);
+            computer.track_self_modification();
+            computer.run(vec![]);
+            assert_eq!(
+                computer.self_modifications(),
+                &[SelfModification { writer: 0, addr: 1, old: 1, new: 2 }],
+                "{:?}",
+                engine
+            );
+            assert_eq!(
+                computer.self_modifications()[0].to_string(),
+                "instruction at 0 overwrote 1 with 2 (was 1)"
+            );
+
+            let mut untracked = IntcodeComputer::new(&parse_program("1001,1,1,1,99").unwrap());
+            untracked.set_engine(*engine);
+            untracked.run(vec![]);
+            assert!(untracked.self_modifications().is_empty(), "{:?}", engine);
+        }
+    }
+
     #[test]
     fn large_number() {
-        let prog = parse_program("104,1125899906842624,99");
+        let prog = parse_program("104,1125899906842624,99").unwrap();
         assert_eq!(
             *IntcodeComputer::new(&prog).run(vec![]).last().unwrap(),
             1125899906842624
@@ -0,0 +1,141 @@
+use crate::binary::{self, DecodeError};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ParseError {
+    /// There were no numbers at all.
+    Empty,
+    /// The token at this index, counting from 0, is not a number.
+    InvalidNumber { index: usize, token: String },
+    /// The program is in the binary format but could not be decoded.
+    Binary(DecodeError),
+    Io(io::Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "program is empty"),
+            ParseError::InvalidNumber { index, token } => {
+                write!(f, "token {} ({:?}) is not a number", index, token)
+            }
+            ParseError::Binary(err) => write!(f, "invalid binary program: {}", err),
+            ParseError::Io(err) => write!(f, "could not read program: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Binary(err) => Some(err),
+            ParseError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> ParseError {
+        ParseError::Io(err)
+    }
+}
+
+/// Parses comma separated numbers. Whitespace around the program and around
+/// each number is ignored.
+pub fn parse_program(program: &str) -> Result<Vec<i64>, ParseError> {
+    let program = program.trim();
+    if program.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    program
+        .split(',')
+        .enumerate()
+        .map(|(index, token)| {
+            let token = token.trim();
+            token.parse::<i64>().map_err(|_| ParseError::InvalidNumber {
+                index,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses a program in either the text or the binary format.
+fn program_from_bytes(bytes: &[u8]) -> Result<Vec<i64>, ParseError> {
+    if binary::is_binary(bytes) {
+        return binary::decode_program(bytes).map_err(ParseError::Binary);
+    }
+
+    let text = std::str::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    parse_program(text)
+}
+
+/// Reads a whole program from `reader`, such as stdin, in either format.
+pub fn read_program(mut reader: impl Read) -> Result<Vec<i64>, ParseError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    program_from_bytes(&bytes)
+}
+
+/// Loads a program file in either the text or the binary format.
+pub fn load_program(path: impl AsRef<Path>) -> Result<Vec<i64>, ParseError> {
+    program_from_bytes(&fs::read(path)?)
+}
+
+/// Loads the program at `path` if there is one, otherwise parses `default`,
+/// usually a puzzle input built into the binary. Panics if the program can't
+/// be loaded, as there is nothing else to run.
+pub fn program_or_default(path: Option<&str>, default: &str) -> Vec<i64> {
+    match path {
+        Some(path) => load_program(path).unwrap_or_else(|err| panic!("Could not load {}: {}", path, err)),
+        None => parse_program(default).unwrap(),
+    }
+}
+
+/// The program named by the first command line argument, or `default`.
+pub fn program_from_args(default: &str) -> Vec<i64> {
+    program_or_default(std::env::args().nth(1).as_deref(), default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_with_whitespace() {
+        assert_eq!(parse_program(" 1, 2,-3,\n99\n").unwrap(), vec![1, 2, -3, 99]);
+        assert_eq!(read_program("104,7,99\r\n".as_bytes()).unwrap(), vec![104, 7, 99]);
+        let bytes = binary::encode_program(&[104, 7, 99], true);
+        assert_eq!(read_program(&bytes[..]).unwrap(), vec![104, 7, 99]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        match parse_program("1,2,x3,99") {
+            Err(ParseError::InvalidNumber { index: 2, token }) => assert_eq!(token, "x3"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(matches!(parse_program("1,,99"), Err(ParseError::InvalidNumber { index: 1, .. })));
+        assert!(matches!(parse_program(" \n"), Err(ParseError::Empty)));
+        assert!(matches!(load_program("does/not/exist.txt"), Err(ParseError::Io(_))));
+        assert!(matches!(read_program(&b"ICB1\x07"[..]), Err(ParseError::Binary(_))));
+        assert_eq!(
+            parse_program("1,2,x3").unwrap_err().to_string(),
+            "token 2 (\"x3\") is not a number"
+        );
+    }
+
+    #[test]
+    fn default_programs() {
+        assert_eq!(program_or_default(None, "104,7,99"), vec![104, 7, 99]);
+        let path = std::env::temp_dir().join(format!("intcode_program_{}.txt", std::process::id()));
+        fs::write(&path, "104,8,99").unwrap();
+        assert_eq!(program_or_default(path.to_str(), "104,7,99"), vec![104, 8, 99]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+//! Recording and replaying a program's I/O, so an interactive session can be
+//! kept as a regression test.
+//!
+//! A session file has one event per line: `in` or `out`, the number of
+//! instructions executed when it happened, and the value, such as `out 412 7`.
+//! Blank lines and lines starting with `#` are ignored.
+
+use crate::{IntcodeComputer, Output};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// A value read by an input instruction.
+    Input { at: u64, value: i64 },
+    Output { at: u64, value: i64 },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Input { at, value } => write!(f, "in {} {}", at, value),
+            Event::Output { at, value } => write!(f, "out {} {}", at, value),
+        }
+    }
+}
+
+impl Event {
+    /// Number of instructions executed when the event happened.
+    pub fn at(&self) -> u64 {
+        match self {
+            Event::Input { at, .. } | Event::Output { at, .. } => *at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    pub fn inputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.events.iter().filter_map(|event| match event {
+            Event::Input { value, .. } => Some(*value),
+            _ => None,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        self.events.iter().map(|event| format!("{}\n", event)).collect()
+    }
+
+    pub fn parse(text: &str) -> Result<Recording, String> {
+        let mut events = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            let parsed = match fields[..] {
+                [kind, at, value] => at.parse().ok().zip(value.parse().ok()).and_then(|(at, value)| match kind {
+                    "in" => Some(Event::Input { at, value }),
+                    "out" => Some(Event::Output { at, value }),
+                    _ => None,
+                }),
+                _ => None,
+            };
+            events.push(parsed.ok_or_else(|| format!("line {}: invalid event {:?}", number + 1, line))?);
+        }
+
+        Ok(Recording { events })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Recording> {
+        Recording::parse(&fs::read_to_string(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Where a replay first stopped matching its recording.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    /// Index of the event in the recording.
+    pub index: usize,
+    /// `None` if the replay produced more events than were recorded.
+    pub expected: Option<Event>,
+    /// `None` if the replay ended before producing this event.
+    pub actual: Option<Event>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.expected, &self.actual) {
+            (Some(expected), Some(actual)) => {
+                write!(f, "event {}: expected `{}`, got `{}`", self.index, expected, actual)
+            }
+            (Some(expected), None) => write!(f, "event {}: expected `{}`, but the run ended", self.index, expected),
+            (None, Some(actual)) => write!(f, "event {}: unexpected `{}`", self.index, actual),
+            (None, None) => write!(f, "event {}", self.index),
+        }
+    }
+}
+
+/// Runs `program` with the recorded inputs and checks that it produces exactly
+/// the recorded events, at the same instruction counts. The run stops once the
+/// program halts, runs out of input, or passes the last recorded event.
+pub fn replay(program: &[i64], recording: &Recording) -> Result<(), Mismatch> {
+    let mut computer = IntcodeComputer::new(program);
+    computer.record_io();
+    for input in recording.inputs() {
+        computer.queue_input(input);
+    }
+
+    let end = recording.events.last().map_or(0, Event::at);
+    let mut checked = 0;
+    loop {
+        let stopped = match computer.step() {
+            Some(Output::Exit) | Some(Output::WaitingForInput) => true,
+            _ => computer.instruction_count() > end,
+        };
+
+        let actual = &computer.recording().unwrap().events;
+        for (index, event) in actual.iter().enumerate().skip(checked) {
+            let expected = recording.events.get(index).copied();
+            if expected != Some(*event) {
+                return Err(Mismatch {
+                    index,
+                    expected,
+                    actual: Some(*event),
+                });
+            }
+        }
+        checked = actual.len();
+
+        if stopped {
+            break;
+        }
+    }
+
+    match recording.events.get(checked) {
+        Some(expected) => Err(Mismatch {
+            index: checked,
+            expected: Some(*expected),
+            actual: None,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, Engine};
+
+    /// Reads numbers and outputs each one doubled, until given 0.
+    fn doubler() -> Vec<i64> {
+        parse_program("3,20,1006,20,14,1002,20,2,21,4,21,1105,1,0,99").unwrap()
+    }
+
+    fn session(inputs: &[i64], engine: Engine) -> Recording {
+        let mut computer = IntcodeComputer::new(&doubler());
+        computer.set_engine(engine);
+        computer.record_io();
+        for input in inputs {
+            computer.outputs(vec![*input]).for_each(drop);
+        }
+        computer.recording().unwrap().clone()
+    }
+
+    #[test]
+    fn record_with_timestamps() {
+        let recording = session(&[4, 0], Engine::Interpreter);
+        assert_eq!(recording.to_text(), "in 1 4\nout 4 8\nin 6 0\n");
+        assert_eq!(session(&[4, 0], Engine::Compiled), recording);
+        assert_eq!(Recording::parse(&recording.to_text()), Ok(recording));
+        assert!(Recording::parse("in 1\n").is_err());
+    }
+
+    #[test]
+    fn replay_matches() {
+        let recording = session(&[3, 5, 0], Engine::Compiled);
+        assert_eq!(replay(&doubler(), &recording), Ok(()));
+
+        let mut changed = doubler();
+        changed[7] = 3;
+        let mismatch = replay(&changed, &recording).unwrap_err();
+        assert_eq!(mismatch.index, 1);
+        assert_eq!(mismatch.to_string(), "event 1: expected `out 4 6`, got `out 4 9`");
+
+        let mut longer = recording.clone();
+        longer.events.push(Event::Output { at: 20, value: 1 });
+        assert_eq!(
+            replay(&doubler(), &longer).unwrap_err().to_string(),
+            "event 5: expected `out 20 1`, but the run ended"
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{IntcodeComputer, Output};
+use std::fmt;
+
+/// How the scheduler picks which machine runs next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// One step of each runnable machine in turn.
+    RoundRobin,
+    /// A machine keeps running until it blocks or halts, then the next one
+    /// gets a turn.
+    UntilBlocked,
+    /// A runnable machine picked at random for every step. Seeded, so runs are
+    /// repeatable.
+    Random { seed: u64 },
+}
+
+/// A stretch of the interleaving in which `machine` ran `steps` steps in a row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Turn {
+    pub machine: usize,
+    pub steps: u64,
+}
+
+impl fmt::Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.machine, self.steps)
+    }
+}
+
+/// Renders a log as space separated turns, such as `0x12 1x3`.
+pub fn format_log(log: &[Turn]) -> String {
+    log.iter().map(Turn::to_string).collect::<Vec<String>>().join(" ")
+}
+
+/// Reads a log written by `format_log`.
+pub fn parse_log(log: &str) -> Option<Vec<Turn>> {
+    log.split_whitespace()
+        .map(|turn| {
+            let (machine, steps) = turn.split_once('x')?;
+            Some(Turn {
+                machine: machine.parse().ok()?,
+                steps: steps.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Why a run stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    /// Every machine halted.
+    Halted,
+    /// Some machines have not halted, but none can run as they are all
+    /// waiting for input.
+    Deadlock,
+    StepLimit,
+}
+
+/// A replay asked a machine to step when it could not.
+#[derive(Debug, PartialEq)]
+pub struct Diverged {
+    /// Index of the turn in the log.
+    pub turn: usize,
+}
+
+/// Runs several machines that feed each other, choosing who steps next with a
+/// `Policy`. Every step taken is logged, so a run can be replayed exactly.
+pub struct Scheduler {
+    machines: Vec<IntcodeComputer>,
+    routes: Vec<Option<usize>>,
+    outputs: Vec<Vec<i64>>,
+    policy: Policy,
+    rng: u64,
+    current: usize,
+    log: Vec<Turn>,
+}
+
+impl Scheduler {
+    pub fn new(policy: Policy) -> Scheduler {
+        let rng = match policy {
+            Policy::Random { seed } => seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+            _ => 0,
+        };
+
+        Scheduler {
+            machines: vec![],
+            routes: vec![],
+            outputs: vec![],
+            policy,
+            rng,
+            current: 0,
+            log: vec![],
+        }
+    }
+
+    /// Adds a machine and returns its index.
+    pub fn add(&mut self, computer: IntcodeComputer) -> usize {
+        self.machines.push(computer);
+        self.routes.push(None);
+        self.outputs.push(vec![]);
+        self.machines.len() - 1
+    }
+
+    /// Queues every output of machine `from` as an input of machine `to`.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.machines.len(), "No machine {}", to);
+        self.routes[from] = Some(to);
+    }
+
+    pub fn machine(&self, index: usize) -> &IntcodeComputer {
+        &self.machines[index]
+    }
+
+    pub fn machine_mut(&mut self, index: usize) -> &mut IntcodeComputer {
+        &mut self.machines[index]
+    }
+
+    /// Everything machine `index` has output, whether or not it was routed.
+    pub fn outputs(&self, index: usize) -> &[i64] {
+        &self.outputs[index]
+    }
+
+    /// The interleaving so far.
+    pub fn log(&self) -> &[Turn] {
+        &self.log
+    }
+
+    fn runnable(&self, index: usize) -> bool {
+        let machine = &self.machines[index];
+        let blocked = machine.waiting_for_input() && machine.inputs.is_empty();
+        !(machine.finished() || blocked)
+    }
+
+    /// The first runnable machine at or after `start`, wrapping around.
+    fn next_runnable(&self, start: usize) -> Option<usize> {
+        let count = self.machines.len();
+        (0..count)
+            .map(|i| (start + i) % count)
+            .find(|i| self.runnable(*i))
+    }
+
+    fn pick(&mut self) -> Option<usize> {
+        match self.policy {
+            Policy::RoundRobin => {
+                let after_last = self.log.last().map_or(0, |turn| turn.machine + 1);
+                self.next_runnable(after_last % self.machines.len().max(1))
+            }
+            Policy::UntilBlocked => {
+                self.current = self.next_runnable(self.current)?;
+                Some(self.current)
+            }
+            Policy::Random { .. } => {
+                let runnable = (0..self.machines.len())
+                    .filter(|i| self.runnable(*i))
+                    .collect::<Vec<usize>>();
+                if runnable.is_empty() {
+                    return None;
+                }
+
+                // xorshift64*
+                self.rng ^= self.rng >> 12;
+                self.rng ^= self.rng << 25;
+                self.rng ^= self.rng >> 27;
+                let roll = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d);
+                Some(runnable[(roll % runnable.len() as u64) as usize])
+            }
+        }
+    }
+
+    fn step_machine(&mut self, index: usize) {
+        if let Some(Output::OutputVal(val)) = self.machines[index].step() {
+            self.outputs[index].push(val);
+            if let Some(to) = self.routes[index] {
+                self.machines[to].queue_input(val);
+            }
+        }
+
+        match self.log.last_mut() {
+            Some(turn) if turn.machine == index => turn.steps += 1,
+            _ => self.log.push(Turn { machine: index, steps: 1 }),
+        }
+    }
+
+    /// Steps machines until they have all halted or are all blocked, or for at
+    /// most `max_steps` steps.
+    pub fn run(&mut self, max_steps: u64) -> Stop {
+        for _ in 0..max_steps {
+            match self.pick() {
+                Some(index) => self.step_machine(index),
+                None if self.machines.iter().all(IntcodeComputer::finished) => return Stop::Halted,
+                None => return Stop::Deadlock,
+            }
+        }
+
+        Stop::StepLimit
+    }
+
+    /// Steps machines exactly as `log` says, ignoring the policy. Machines must
+    /// be in the state they were in when the log was recorded.
+    pub fn replay(&mut self, log: &[Turn]) -> Result<(), Diverged> {
+        for (turn, Turn { machine, steps }) in log.iter().enumerate() {
+            for _ in 0..*steps {
+                if *machine >= self.machines.len() || !self.runnable(*machine) {
+                    return Err(Diverged { turn });
+                }
+                self.step_machine(*machine);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn amplifiers(policy: Policy) -> Scheduler {
+        let program = parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        )
+        .unwrap();
+
+        let mut scheduler = Scheduler::new(policy);
+        for phase in &[9, 8, 7, 6, 5] {
+            let index = scheduler.add(IntcodeComputer::new(&program));
+            scheduler.machine_mut(index).queue_input(*phase);
+        }
+        for i in 0..5 {
+            scheduler.connect(i, (i + 1) % 5);
+        }
+        scheduler.machine_mut(0).queue_input(0);
+        scheduler
+    }
+
+    #[test]
+    fn feedback_loop_under_every_policy() {
+        for policy in &[Policy::RoundRobin, Policy::UntilBlocked, Policy::Random { seed: 7 }] {
+            let mut scheduler = amplifiers(*policy);
+            assert_eq!(scheduler.run(100_000), Stop::Halted);
+            assert_eq!(scheduler.outputs(4).last(), Some(&139629729));
+        }
+    }
+
+    #[test]
+    fn replay_reproduces_interleaving() {
+        // Two machines each send their id three times to a third that echoes
+        // what it receives, so its output depends on the interleaving
+        let sender = |id| parse_program(&format!("104,{0},104,{0},104,{0},99", id)).unwrap();
+        let echo = parse_program("3,100,4,100,1105,1,0").unwrap();
+        let build = |policy| {
+            let mut scheduler = Scheduler::new(policy);
+            scheduler.add(IntcodeComputer::new(&sender(1)));
+            scheduler.add(IntcodeComputer::new(&sender(2)));
+            scheduler.add(IntcodeComputer::new(&echo));
+            scheduler.connect(0, 2);
+            scheduler.connect(1, 2);
+            scheduler
+        };
+
+        let mut until_blocked = build(Policy::UntilBlocked);
+        assert_eq!(until_blocked.run(1_000), Stop::Deadlock);
+        assert_eq!(until_blocked.outputs(2), &[1, 1, 1, 2, 2, 2]);
+
+        let mut random = build(Policy::Random { seed: 3 });
+        assert_eq!(random.run(1_000), Stop::Deadlock);
+        assert_eq!(random.outputs(2), &[2, 1, 2, 1, 1, 2]);
+        let log = parse_log(&format_log(random.log())).unwrap();
+        assert_eq!(log, random.log());
+
+        let mut replayed = build(Policy::RoundRobin);
+        assert_eq!(replayed.replay(&log), Ok(()));
+        assert_eq!(replayed.outputs(2), random.outputs(2));
+
+        let mut diverging = build(Policy::RoundRobin);
+        assert_eq!(
+            diverging.replay(&[Turn { machine: 2, steps: 2 }]),
+            Err(Diverged { turn: 0 })
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{IntcodeComputer, Output};
+use rayon::prelude::*;
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Faults in `IntcodeComputer` are panics, and patching operands or opcodes
+/// makes them common. Keep the ones caught here off stderr without hiding any
+/// other panic.
+fn install_quiet_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(|quiet| quiet.get()) {
+                default_hook(info);
+            }
+        }));
+    });
+}
+
+/// The state a patched program was left in once it halted.
+pub struct Outcome {
+    pub memory: Vec<i64>,
+    pub outputs: Vec<i64>,
+}
+
+/// Searches for values to patch into a program so that the program's final
+/// state satisfies a predicate. Candidates are evaluated in parallel. A
+/// candidate that asks for more input than it was given, runs past the step
+/// limit without halting, faults (such as on a negative address, an unknown
+/// opcode or an overflow) or grows memory past the memory limit does not match.
+pub struct Search<'a> {
+    program: &'a [i64],
+    inputs: Vec<i64>,
+    patches: Vec<(usize, RangeInclusive<i64>)>,
+    candidates: usize,
+    step_limit: u64,
+    memory_limit: usize,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(program: &'a [i64]) -> Search<'a> {
+        Search {
+            program,
+            inputs: vec![],
+            patches: vec![],
+            candidates: 1,
+            step_limit: 1_000_000,
+            memory_limit: 1 << 20,
+        }
+    }
+
+    /// Tries every value in `values` at `addr`. Assignments are ordered as if by
+    /// nested loops, with the first patched address as the outermost loop.
+    ///
+    /// Panics if `addr` is outside the program, or if there would be more
+    /// candidates than fit in a `usize`.
+    pub fn patch(mut self, addr: usize, values: RangeInclusive<i64>) -> Search<'a> {
+        if addr >= self.program.len() {
+            panic!("Patch address {} is outside the program of {} words", addr, self.program.len());
+        }
+        self.candidates = Search::range_len(&values)
+            .and_then(|len| self.candidates.checked_mul(len))
+            .expect("Too many candidates to search");
+        self.patches.push((addr, values));
+        self
+    }
+
+    /// Instructions each candidate may run before it is given up on.
+    pub fn step_limit(mut self, steps: u64) -> Search<'a> {
+        self.step_limit = steps;
+        self
+    }
+
+    /// Words of memory each candidate may grow to, 2^20 unless set.
+    pub fn memory_limit(mut self, words: usize) -> Search<'a> {
+        self.memory_limit = words;
+        self
+    }
+
+    /// Inputs given to every candidate run.
+    pub fn inputs(mut self, inputs: Vec<i64>) -> Search<'a> {
+        self.inputs = inputs;
+        self
+    }
+
+    /// `None` if the range has more values than fit in a `usize`.
+    fn range_len(range: &RangeInclusive<i64>) -> Option<usize> {
+        let len = (*range.end() as i128 - *range.start() as i128 + 1).max(0);
+        usize::try_from(len).ok()
+    }
+
+    fn assignment(&self, mut index: usize) -> Vec<i64> {
+        let mut values = vec![0; self.patches.len()];
+        for (i, (_, range)) in self.patches.iter().enumerate().rev() {
+            let len = Search::range_len(range).unwrap();
+            values[i] = range.start().wrapping_add((index % len) as i64);
+            index /= len;
+        }
+
+        values
+    }
+
+    /// `None` if the candidate starved for input, did not halt in time or
+    /// faulted.
+    fn evaluate(&self, assignment: &[i64]) -> Option<Outcome> {
+        let mut patched = self.program.to_vec();
+        for ((addr, _), value) in self.patches.iter().zip(assignment) {
+            patched[*addr] = *value;
+        }
+
+        let mut computer = IntcodeComputer::new(&patched);
+        computer.set_memory_limit(self.memory_limit);
+        for input in &self.inputs {
+            computer.queue_input(*input);
+        }
+
+        install_quiet_panic_hook();
+        QUIET.with(|quiet| quiet.set(true));
+        let outputs = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut outputs = vec![];
+            loop {
+                match computer.tick_within(self.step_limit.saturating_sub(computer.instruction_count()))? {
+                    Output::OutputVal(val) => outputs.push(val),
+                    Output::WaitingForInput => return None,
+                    Output::Exit => return Some(outputs),
+                }
+            }
+        }));
+        QUIET.with(|quiet| quiet.set(false));
+
+        Some(Outcome {
+            memory: computer.memory,
+            outputs: outputs.ok()??,
+        })
+    }
+
+    fn matches<F>(&self, assignment: &[i64], predicate: &F) -> bool
+    where
+        F: Fn(&Outcome) -> bool,
+    {
+        self.evaluate(assignment).is_some_and(|outcome| predicate(&outcome))
+    }
+
+    /// Returns the first assignment, in nested loop order, whose outcome satisfies
+    /// the predicate. Stops evaluating candidates once a match is known.
+    pub fn find_first<F>(&self, predicate: F) -> Option<Vec<i64>>
+    where
+        F: Fn(&Outcome) -> bool + Sync,
+    {
+        (0..self.candidates)
+            .into_par_iter()
+            .map(|i| self.assignment(i))
+            .find_first(|assignment| self.matches(assignment, &predicate))
+    }
+
+    /// Returns every assignment whose outcome satisfies the predicate, in nested
+    /// loop order.
+    pub fn find_all<F>(&self, predicate: F) -> Vec<Vec<i64>>
+    where
+        F: Fn(&Outcome) -> bool + Sync,
+    {
+        (0..self.candidates)
+            .into_par_iter()
+            .map(|i| self.assignment(i))
+            .filter(|assignment| self.matches(assignment, &predicate))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn find_first_in_loop_order() {
+        let program = parse_program("1101,0,0,0,99").unwrap();
+        let search = Search::new(&program).patch(1, 0..=4).patch(2, 0..=4);
+        assert_eq!(search.find_first(|o| o.memory[0] == 5), Some(vec![1, 4]));
+        assert_eq!(search.find_first(|o| o.memory[0] == 9), None);
+    }
+
+    #[test]
+    fn find_all_matches() {
+        let program = parse_program("1101,0,0,0,99").unwrap();
+        let search = Search::new(&program).patch(1, 0..=4).patch(2, 0..=4);
+        assert_eq!(
+            search.find_all(|o| o.memory[0] == 5),
+            vec![vec![1, 4], vec![2, 3], vec![3, 2], vec![4, 1]]
+        );
+    }
+
+    #[test]
+    fn match_on_outputs() {
+        let program = parse_program("3,0,1001,0,0,0,4,0,99").unwrap();
+        let search = Search::new(&program).inputs(vec![10]).patch(4, -20..=20);
+        assert_eq!(search.find_first(|o| o.outputs == vec![3]), Some(vec![-7]));
+    }
+
+    #[test]
+    fn runaway_candidates_do_not_match() {
+        // Loops forever unless the condition is 0, then reads an input and halts
+        let program = parse_program("1105,0,0,3,9,99").unwrap();
+        let search = Search::new(&program).step_limit(100).patch(1, 0..=1);
+        assert!(search.find_all(|_| true).is_empty());
+        assert_eq!(search.inputs(vec![7]).find_all(|_| true), vec![vec![0]]);
+    }
+
+    #[test]
+    fn faulting_candidates_do_not_match() {
+        // Negative addresses, an unknown opcode and an overflow all fault
+        let program = parse_program("1,0,0,0,99").unwrap();
+        let search = Search::new(&program).patch(1, -3..=3);
+        assert_eq!(search.find_all(|_| true), vec![vec![0], vec![1], vec![2], vec![3]]);
+        let search = Search::new(&program).patch(0, 0..=3);
+        assert_eq!(search.find_all(|_| true), vec![vec![1], vec![2]]);
+        let program = parse_program(&format!("1102,{},0,0,99", i64::MAX)).unwrap();
+        assert!(Search::new(&program).patch(2, 2..=2).find_all(|_| true).is_empty());
+
+        // As does writing far past the program
+        let program = parse_program("1101,1,1,0,99").unwrap();
+        let search = Search::new(&program).memory_limit(10).patch(3, 5..=1_000);
+        assert_eq!(search.find_all(|_| true), (5..10).map(|addr| vec![addr]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn huge_ranges() {
+        assert_eq!(Search::range_len(&(i64::MIN..=i64::MAX)), None);
+        assert_eq!(Search::range_len(&(-5..=5)), Some(11));
+
+        let program = parse_program("99,0").unwrap();
+        let search = Search::new(&program).patch(1, i64::MAX - 1..=i64::MAX);
+        assert_eq!(search.find_all(|_| true), vec![vec![i64::MAX - 1], vec![i64::MAX]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the program")]
+    fn patch_outside_the_program() {
+        let program = parse_program("99").unwrap();
+        Search::new(&program).patch(1, 0..=1);
+    }
+}
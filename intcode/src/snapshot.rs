@@ -0,0 +1,75 @@
+use crate::IntcodeComputer;
+
+/// A copy of a computer's execution state. Registered opcodes and attached
+/// devices are not part of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub memory: Vec<i64>,
+    pub pointer: usize,
+    pub relative_base: i64,
+    pub instructions_executed: u64,
+    /// Inputs queued but not yet read, in the order they will be read.
+    pub inputs: Vec<i64>,
+    /// Where the pending input instruction will store its input, if waiting.
+    pub wait_for_input_addr: Option<usize>,
+    pub finished: bool,
+}
+
+impl Snapshot {
+    /// Memory at `addr`, where addresses past the end read as 0.
+    pub fn read(&self, addr: usize) -> i64 {
+        self.memory.get(addr).copied().unwrap_or(0)
+    }
+}
+
+impl IntcodeComputer {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            pointer: self.pointer,
+            relative_base: self.relative_base,
+            instructions_executed: self.instructions_executed,
+            inputs: self.inputs.iter().copied().collect(),
+            wait_for_input_addr: self.wait_for_input_addr,
+            finished: self.has_exited,
+        }
+    }
+
+    /// Puts the computer back into a previously saved state.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = snapshot.memory.clone();
+        self.compiled.clear();
+        self.pointer = snapshot.pointer;
+        self.relative_base = snapshot.relative_base;
+        self.instructions_executed = snapshot.instructions_executed;
+        self.inputs = snapshot.inputs.iter().copied().collect();
+        self.wait_for_input_addr = snapshot.wait_for_input_addr;
+        self.has_exited = snapshot.finished;
+    }
+
+    pub fn from_snapshot(snapshot: &Snapshot) -> IntcodeComputer {
+        let mut computer = IntcodeComputer::new(&[]);
+        computer.restore(snapshot);
+        computer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn restore_and_rerun() {
+        let prog = parse_program("3,9,8,9,10,9,4,9,99,-1,8").unwrap();
+        let mut computer = IntcodeComputer::new(&prog);
+        let start = computer.snapshot();
+
+        assert_eq!(computer.run(vec![8]), vec![1]);
+        assert_ne!(computer.snapshot(), start);
+
+        computer.restore(&start);
+        assert_eq!(computer.run(vec![7]), vec![0]);
+        assert_eq!(IntcodeComputer::from_snapshot(&start).run(vec![8]), vec![1]);
+    }
+}
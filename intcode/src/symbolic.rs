@@ -0,0 +1,595 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// A value computed by a program, in terms of the symbols it was given.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Const(i64),
+    Var(String),
+    Add(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+    /// A read through an address that is itself symbolic. Opaque to the solver.
+    Load(Box<Expr>),
+}
+
+/// An expression of the form `constant + sum(coefficient * symbol)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Linear {
+    pub constant: i64,
+    pub terms: BTreeMap<String, i64>,
+}
+
+impl Linear {
+    /// `None` if a coefficient or the constant overflows.
+    fn add(mut self, other: Linear) -> Option<Linear> {
+        self.constant = self.constant.checked_add(other.constant)?;
+        for (name, coefficient) in other.terms {
+            let sum = self.terms.entry(name).or_insert(0);
+            *sum = sum.checked_add(coefficient)?;
+        }
+        self.terms.retain(|_, c| *c != 0);
+        Some(self)
+    }
+
+    /// `None` if a coefficient or the constant overflows.
+    fn scale(mut self, factor: i64) -> Option<Linear> {
+        self.constant = self.constant.checked_mul(factor)?;
+        for coefficient in self.terms.values_mut() {
+            *coefficient = coefficient.checked_mul(factor)?;
+        }
+        self.terms.retain(|_, c| *c != 0);
+        Some(self)
+    }
+
+    fn into_expr(self) -> Expr {
+        self.terms
+            .into_iter()
+            .map(|(name, coefficient)| match coefficient {
+                1 => Expr::Var(name),
+                c => Expr::Multiply(Box::new(Expr::Const(c)), Box::new(Expr::Var(name))),
+            })
+            .chain(std::iter::once(Expr::Const(self.constant)).filter(|c| *c != Expr::Const(0)))
+            .fold(None, |acc, term| match acc {
+                None => Some(term),
+                Some(acc) => Some(Expr::Add(Box::new(acc), Box::new(term))),
+            })
+            .unwrap_or(Expr::Const(0))
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (name, coefficient) in &self.terms {
+            let sign = if *coefficient < 0 { "-" } else { "+" };
+            if first {
+                write!(f, "{}", if *coefficient < 0 { "-" } else { "" })?;
+            } else {
+                write!(f, " {} ", sign)?;
+            }
+            match coefficient.abs() {
+                1 => write!(f, "{}", name)?,
+                c => write!(f, "{}*{}", c, name)?,
+            }
+            first = false;
+        }
+
+        if first {
+            write!(f, "{}", self.constant)
+        } else if self.constant != 0 {
+            let sign = if self.constant < 0 { "-" } else { "+" };
+            write!(f, " {} {}", sign, self.constant.abs())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Expr {
+    pub fn as_const(&self) -> Option<i64> {
+        match self {
+            Expr::Const(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    /// The expression in linear form, if it has one that fits in an `i64`.
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(c) => Some(Linear {
+                constant: *c,
+                terms: BTreeMap::new(),
+            }),
+            Expr::Var(name) => Some(Linear {
+                constant: 0,
+                terms: std::iter::once((name.clone(), 1)).collect(),
+            }),
+            Expr::Add(a, b) => a.linear()?.add(b.linear()?),
+            Expr::Multiply(a, b) => match (a.as_const(), b.as_const()) {
+                (Some(k), _) => b.linear()?.scale(k),
+                (_, Some(k)) => a.linear()?.scale(k),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn add(a: Expr, b: Expr) -> Expr {
+        let sum = Expr::Add(Box::new(a), Box::new(b));
+        sum.linear().map(Linear::into_expr).unwrap_or(sum)
+    }
+
+    fn multiply(a: Expr, b: Expr) -> Expr {
+        let product = Expr::Multiply(Box::new(a), Box::new(b));
+        product.linear().map(Linear::into_expr).unwrap_or(product)
+    }
+
+    fn less_than(a: Expr, b: Expr) -> Expr {
+        match (a.as_const(), b.as_const()) {
+            (Some(x), Some(y)) => Expr::Const(if x < y { 1 } else { 0 }),
+            _ => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn equals(a: Expr, b: Expr) -> Expr {
+        match (a.as_const(), b.as_const()) {
+            (Some(x), Some(y)) => Expr::Const(if x == y { 1 } else { 0 }),
+            _ if a == b => Expr::Const(1),
+            _ => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(linear) = self.linear() {
+            return write!(f, "{}", linear);
+        }
+
+        match self {
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Multiply(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(addr) => write!(f, "[{}]", addr),
+            Expr::Const(_) | Expr::Var(_) => unreachable!(),
+        }
+    }
+}
+
+/// A branch decision taken on a symbolic condition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub condition: Expr,
+    pub nonzero: bool,
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} 0", self.condition, if self.nonzero { "!=" } else { "==" })
+    }
+}
+
+/// One way through the program, from the start to a halt instruction.
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub memory: Vec<Expr>,
+    pub outputs: Vec<Expr>,
+    pub constraints: Vec<Constraint>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SymbolicError {
+    /// The instruction at this address is not a known constant opcode.
+    InvalidInstruction(usize),
+    /// The instruction at this address writes through a symbolic address.
+    SymbolicWrite(usize),
+    /// The instruction at this address jumps to a symbolic target.
+    SymbolicJump(usize),
+    /// The instruction at this address adjusts the relative base by a symbolic amount.
+    SymbolicRelativeBase(usize),
+    /// Exploration ran for more steps than allowed.
+    StepLimit,
+    /// A symbol was placed at this address, past the end of the program.
+    SymbolAddress(usize),
+    /// The instruction at this address uses or jumps to a negative address.
+    NegativeAddress(usize),
+    /// The instruction at this address uses an address past the memory limit.
+    MemoryLimit(usize),
+    /// Exploring or solving needed a value too large for an `i64`.
+    Overflow,
+}
+
+#[derive(Clone)]
+struct State {
+    memory: Vec<Expr>,
+    pointer: usize,
+    relative_base: i64,
+    memory_limit: usize,
+    inputs_read: usize,
+    outputs: Vec<Expr>,
+    constraints: Vec<Constraint>,
+}
+
+impl State {
+    fn read(&mut self, addr: usize) -> Expr {
+        self.memory.get(addr).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    fn write(&mut self, addr: usize, val: Expr) {
+        if addr >= self.memory.len() {
+            self.memory.resize(addr + 1, Expr::Const(0));
+        }
+        self.memory[addr] = val;
+    }
+
+    fn mode(instr: i64, param: u32) -> i64 {
+        instr / 10_i64.pow(param + 2) % 10
+    }
+
+    /// `Ok(None)` if the address is symbolic.
+    fn address(&mut self, at: usize, instr: i64, param: u32) -> Result<Option<usize>, SymbolicError> {
+        let operand = self.read(at + 1 + param as usize);
+        let addr = match (State::mode(instr, param), operand.as_const()) {
+            (1, _) => return Ok(Some(at + 1 + param as usize)),
+            (_, None) => return Ok(None),
+            (2, Some(offset)) => self.relative_base.checked_add(offset).ok_or(SymbolicError::Overflow)?,
+            (_, Some(addr)) => addr,
+        };
+
+        let addr = usize::try_from(addr).map_err(|_| SymbolicError::NegativeAddress(at))?;
+        if addr >= self.memory_limit {
+            return Err(SymbolicError::MemoryLimit(at));
+        }
+        Ok(Some(addr))
+    }
+
+    fn parameter(&mut self, at: usize, instr: i64, param: u32) -> Result<Expr, SymbolicError> {
+        let operand = self.read(at + 1 + param as usize);
+        if State::mode(instr, param) == 1 {
+            return Ok(operand);
+        }
+
+        Ok(match self.address(at, instr, param)? {
+            Some(addr) => self.read(addr),
+            None if State::mode(instr, param) == 2 => Expr::Load(Box::new(Expr::add(
+                Expr::Const(self.relative_base),
+                operand,
+            ))),
+            None => Expr::Load(Box::new(operand)),
+        })
+    }
+}
+
+/// Runs a program on symbols instead of concrete values, following both sides
+/// of any branch whose condition depends on a symbol. Path constraints are
+/// recorded but not checked for satisfiability.
+pub struct Symbolic {
+    program: Vec<i64>,
+    symbols: Vec<(usize, String)>,
+    max_steps: usize,
+    memory_limit: usize,
+}
+
+impl Symbolic {
+    pub fn new(program: &[i64]) -> Symbolic {
+        Symbolic {
+            program: program.to_vec(),
+            symbols: vec![],
+            max_steps: 1_000_000,
+            memory_limit: 1 << 20,
+        }
+    }
+
+    /// Treats the value at `addr` as the named symbol. Inputs are always symbolic,
+    /// and are named `input0`, `input1`, ... in the order they are read.
+    pub fn symbol(mut self, addr: usize, name: &str) -> Symbolic {
+        self.symbols.push((addr, name.to_string()));
+        self
+    }
+
+    /// Total number of instructions to execute, across all paths, before giving up.
+    pub fn max_steps(mut self, max_steps: usize) -> Symbolic {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Words of memory the program may use, 2^20 unless set. Using an address
+    /// past the limit is an error rather than growing memory to fit.
+    pub fn memory_limit(mut self, words: usize) -> Symbolic {
+        self.memory_limit = words;
+        self
+    }
+
+    /// Explores every path through the program.
+    pub fn explore(&self) -> Result<Vec<Path>, SymbolicError> {
+        let mut memory = self.program.iter().map(|v| Expr::Const(*v)).collect::<Vec<Expr>>();
+        for (addr, name) in &self.symbols {
+            let slot = memory.get_mut(*addr).ok_or(SymbolicError::SymbolAddress(*addr))?;
+            *slot = Expr::Var(name.clone());
+        }
+
+        let mut pending = vec![State {
+            memory,
+            pointer: 0,
+            relative_base: 0,
+            memory_limit: self.memory_limit,
+            inputs_read: 0,
+            outputs: vec![],
+            constraints: vec![],
+        }];
+        let mut paths = vec![];
+        let mut steps = 0;
+
+        while let Some(mut state) = pending.pop() {
+            loop {
+                steps += 1;
+                if steps > self.max_steps {
+                    return Err(SymbolicError::StepLimit);
+                }
+
+                let at = state.pointer;
+                let instr = state
+                    .read(at)
+                    .as_const()
+                    .ok_or(SymbolicError::InvalidInstruction(at))?;
+                if (0..3).any(|param| State::mode(instr, param) > 2) {
+                    return Err(SymbolicError::InvalidInstruction(at));
+                }
+
+                match instr % 100 {
+                    1 | 2 | 7 | 8 => {
+                        let p1 = state.parameter(at, instr, 0)?;
+                        let p2 = state.parameter(at, instr, 1)?;
+                        let addr = state
+                            .address(at, instr, 2)?
+                            .ok_or(SymbolicError::SymbolicWrite(at))?;
+                        let val = match instr % 100 {
+                            1 => Expr::add(p1, p2),
+                            2 => Expr::multiply(p1, p2),
+                            7 => Expr::less_than(p1, p2),
+                            _ => Expr::equals(p1, p2),
+                        };
+                        state.write(addr, val);
+                        state.pointer += 4;
+                    }
+                    3 => {
+                        let addr = state
+                            .address(at, instr, 0)?
+                            .ok_or(SymbolicError::SymbolicWrite(at))?;
+                        state.write(addr, Expr::Var(format!("input{}", state.inputs_read)));
+                        state.inputs_read += 1;
+                        state.pointer += 2;
+                    }
+                    4 => {
+                        let p1 = state.parameter(at, instr, 0)?;
+                        state.outputs.push(p1);
+                        state.pointer += 2;
+                    }
+                    5 | 6 => {
+                        let condition = state.parameter(at, instr, 0)?;
+                        let target = state
+                            .parameter(at, instr, 1)?
+                            .as_const()
+                            .ok_or(SymbolicError::SymbolicJump(at))?;
+                        let target = usize::try_from(target).map_err(|_| SymbolicError::NegativeAddress(at))?;
+                        let jump_when_nonzero = instr % 100 == 5;
+
+                        match condition.as_const() {
+                            Some(c) if (c != 0) == jump_when_nonzero => state.pointer = target,
+                            Some(_) => state.pointer += 3,
+                            None => {
+                                let mut jumped = state.clone();
+                                jumped.pointer = target;
+                                jumped.constraints.push(Constraint {
+                                    condition: condition.clone(),
+                                    nonzero: jump_when_nonzero,
+                                });
+                                pending.push(jumped);
+
+                                state.pointer += 3;
+                                state.constraints.push(Constraint {
+                                    condition,
+                                    nonzero: !jump_when_nonzero,
+                                });
+                            }
+                        }
+                    }
+                    9 => {
+                        let adjustment = state
+                            .parameter(at, instr, 0)?
+                            .as_const()
+                            .ok_or(SymbolicError::SymbolicRelativeBase(at))?;
+                        state.relative_base = state
+                            .relative_base
+                            .checked_add(adjustment)
+                            .ok_or(SymbolicError::Overflow)?;
+                        state.pointer += 2;
+                    }
+                    99 => {
+                        paths.push(Path {
+                            memory: state.memory,
+                            outputs: state.outputs,
+                            constraints: state.constraints,
+                        });
+                        break;
+                    }
+                    _ => return Err(SymbolicError::InvalidInstruction(at)),
+                }
+            }
+        }
+
+        Ok(paths)
+    }
+}
+
+fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let q = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        Some(q - 1)
+    } else {
+        Some(q)
+    }
+}
+
+fn ceil_div(a: i64, b: i64) -> Option<i64> {
+    floor_div(a.checked_neg()?, b)?.checked_neg()
+}
+
+fn contribution(coefficient: i64, range: &RangeInclusive<i64>) -> Option<(i64, i64)> {
+    let (a, b) = (coefficient.checked_mul(*range.start())?, coefficient.checked_mul(*range.end())?);
+    Some((a.min(b), a.max(b)))
+}
+
+fn solve_terms(
+    terms: &[(String, i64, RangeInclusive<i64>)],
+    remaining: i64,
+    solution: &mut HashMap<String, i64>,
+) -> Option<bool> {
+    let ((name, coefficient, range), rest) = match terms.split_first() {
+        Some(split) => split,
+        None => return Some(remaining == 0),
+    };
+
+    let (rest_min, rest_max) = rest.iter().try_fold((0i64, 0i64), |(lo, hi), (_, c, r)| {
+        let (a, b) = contribution(*c, r)?;
+        Some((lo.checked_add(a)?, hi.checked_add(b)?))
+    })?;
+
+    // The rest of the terms can only make up a bounded amount, which pins this
+    // term to a narrow window. For mixed radix style expressions it is a single value.
+    let (low, high) = (remaining.checked_sub(rest_max)?, remaining.checked_sub(rest_min)?);
+    let (first, last) = if *coefficient > 0 {
+        (ceil_div(low, *coefficient)?, floor_div(high, *coefficient)?)
+    } else {
+        (ceil_div(high, *coefficient)?, floor_div(low, *coefficient)?)
+    };
+
+    for value in first.max(*range.start())..=last.min(*range.end()) {
+        // Within the window, so this term and the rest stay in range
+        if solve_terms(rest, remaining - coefficient * value, solution)? {
+            solution.insert(name.clone(), value);
+            return Some(true);
+        }
+    }
+
+    Some(false)
+}
+
+/// Finds values for the symbols in `expr`, each within its given range, that make
+/// it equal `target`. Only linear expressions are supported, and `Ok(None)` means
+/// there is no solution, or the expression is not linear or has a symbol without
+/// a range.
+pub fn solve_linear(
+    expr: &Expr,
+    target: i64,
+    ranges: &[(&str, RangeInclusive<i64>)],
+) -> Result<Option<HashMap<String, i64>>, SymbolicError> {
+    let linear = match expr.linear() {
+        Some(linear) => linear,
+        None => return Ok(None),
+    };
+    let terms = linear
+        .terms
+        .iter()
+        .map(|(name, coefficient)| {
+            let (_, range) = ranges.iter().find(|(n, _)| n == name)?;
+            Some((name.clone(), *coefficient, range.clone()))
+        })
+        .collect::<Option<Vec<(String, i64, RangeInclusive<i64>)>>>();
+    let mut terms = match terms {
+        Some(terms) => terms,
+        None => return Ok(None),
+    };
+    terms.sort_by_key(|(_, coefficient, _)| std::cmp::Reverse(coefficient.unsigned_abs()));
+
+    let remaining = target.checked_sub(linear.constant).ok_or(SymbolicError::Overflow)?;
+    let mut solution = HashMap::new();
+    match solve_terms(&terms, remaining, &mut solution) {
+        Some(true) => Ok(Some(solution)),
+        Some(false) => Ok(None),
+        None => Err(SymbolicError::Overflow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn closed_form() {
+        let program = parse_program("1,13,14,0,1002,0,100,0,1,0,14,0,99,0,0").unwrap();
+        let paths = Symbolic::new(&program)
+            .symbol(13, "noun")
+            .symbol(14, "verb")
+            .explore()
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].memory[0].to_string(), "100*noun + 101*verb");
+    }
+
+    #[test]
+    fn solve_mixed_radix() {
+        let expr = Expr::add(
+            Expr::multiply(Expr::Const(460800), Expr::Var("noun".to_string())),
+            Expr::add(Expr::Var("verb".to_string()), Expr::Const(797870)),
+        );
+        let solution = solve_linear(&expr, 19690720, &[("noun", 0..=99), ("verb", 0..=99)]).unwrap().unwrap();
+        assert_eq!(solution["noun"], 41);
+        assert_eq!(solution["verb"], 50);
+        assert_eq!(solve_linear(&expr, 0, &[("noun", 0..=99), ("verb", 0..=99)]), Ok(None));
+    }
+
+    #[test]
+    fn overflow_and_bad_symbols() {
+        let term = |name: &str| Expr::multiply(Expr::Const(1 << 40), Expr::Var(name.to_string()));
+        let expr = Expr::add(term("x"), term("y"));
+        let ranges = [("x", 0..=1), ("y", i64::MIN..=i64::MAX)];
+        assert_eq!(solve_linear(&expr, 0, &ranges), Err(SymbolicError::Overflow));
+
+        // Too large to fold into a linear form, so left for the solver to reject
+        let square = Expr::multiply(Expr::Const(i64::MAX), Expr::add(Expr::Var("x".to_string()), Expr::Const(2)));
+        assert_eq!(square.linear(), None);
+
+        let program = parse_program("99").unwrap();
+        let explored = Symbolic::new(&program).symbol(3, "x").explore();
+        assert_eq!(explored.err(), Some(SymbolicError::SymbolAddress(3)));
+    }
+
+    #[test]
+    fn bad_addresses() {
+        let explore = |program: &str| Symbolic::new(&parse_program(program).unwrap()).explore().err();
+        // Writes to -1, jumps to -1, and reads through a relative base that overflows
+        assert_eq!(explore("3,0,1,0,0,-1,99"), Some(SymbolicError::NegativeAddress(2)));
+        assert_eq!(explore("1105,1,-1"), Some(SymbolicError::NegativeAddress(0)));
+        assert_eq!(explore(&format!("109,{},209,1,99", i64::MAX)), Some(SymbolicError::Overflow));
+        assert_eq!(explore("1101,1,1,1048576,99"), Some(SymbolicError::MemoryLimit(0)));
+
+        let program = parse_program("1101,1,1,99,99").unwrap();
+        let explored = Symbolic::new(&program).memory_limit(50).explore();
+        assert_eq!(explored.err(), Some(SymbolicError::MemoryLimit(0)));
+    }
+
+    #[test]
+    fn branch_on_input() {
+        let program = parse_program("3,9,8,9,10,9,4,9,99,-1,8").unwrap();
+        let paths = Symbolic::new(&program).explore().unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].outputs[0].to_string(), "(input0 == 8)");
+
+        let program = parse_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap();
+        let paths = Symbolic::new(&program).explore().unwrap();
+        assert_eq!(paths.len(), 2);
+        for path in paths {
+            assert_eq!(path.constraints.len(), 1);
+            let expected = if path.constraints[0].nonzero { 1 } else { 0 };
+            assert_eq!(path.outputs, vec![Expr::Const(expected)]);
+            assert_eq!(path.constraints[0].condition, Expr::Var("input0".to_string()));
+        }
+    }
+}
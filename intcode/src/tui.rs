@@ -0,0 +1,333 @@
+//! A full screen terminal debugger. `Debugger` holds the state and renders the
+//! screen as a string, so layouts can be tested without a terminal; the
+//! `debugger` binary draws it with `console` and maps keys to `Command`s.
+
+use crate::disasm;
+use crate::inspect::dump_memory;
+use crate::{IntcodeComputer, Output};
+use console::Key;
+use std::collections::BTreeSet;
+
+/// How far `run` goes without reaching a breakpoint before giving up.
+const RUN_LIMIT: u64 = 10_000_000;
+const MEMORY_COLUMNS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Step,
+    /// Run until a breakpoint, or until the program halts or needs input.
+    Run,
+    /// Toggle a breakpoint on the instruction under the cursor.
+    ToggleBreakpoint,
+    CursorUp,
+    CursorDown,
+    MemoryUp,
+    MemoryDown,
+    /// Ask for a value to queue as input.
+    Input,
+    Quit,
+}
+
+impl Command {
+    pub fn from_key(key: &Key) -> Option<Command> {
+        match key {
+            Key::Char('s') | Key::Char(' ') => Some(Command::Step),
+            Key::Char('r') | Key::Char('c') => Some(Command::Run),
+            Key::Char('b') => Some(Command::ToggleBreakpoint),
+            Key::Char('k') | Key::ArrowUp => Some(Command::CursorUp),
+            Key::Char('j') | Key::ArrowDown => Some(Command::CursorDown),
+            Key::Char('[') | Key::ArrowLeft => Some(Command::MemoryUp),
+            Key::Char(']') | Key::ArrowRight => Some(Command::MemoryDown),
+            Key::Char('i') => Some(Command::Input),
+            Key::Char('q') | Key::Escape => Some(Command::Quit),
+            _ => None,
+        }
+    }
+}
+
+pub struct Debugger {
+    computer: IntcodeComputer,
+    breakpoints: BTreeSet<usize>,
+    outputs: Vec<i64>,
+    /// Address of the instruction under the cursor.
+    cursor: usize,
+    memory_start: usize,
+    status: String,
+}
+
+/// Pads or cuts `text` to exactly `width` characters.
+fn fit(text: &str, width: usize) -> String {
+    format!("{:<w$.w$}", text, w = width)
+}
+
+impl Debugger {
+    pub fn new(computer: IntcodeComputer) -> Debugger {
+        Debugger {
+            cursor: computer.pointer,
+            computer,
+            breakpoints: BTreeSet::new(),
+            outputs: vec![],
+            memory_start: 0,
+            status: "Ready".to_string(),
+        }
+    }
+
+    pub fn computer(&self) -> &IntcodeComputer {
+        &self.computer
+    }
+
+    pub fn queue_input(&mut self, input: i64) {
+        self.computer.queue_input(input);
+        self.status = format!("Queued {}", input);
+    }
+
+    pub fn toggle_breakpoint(&mut self, addr: usize) {
+        if !self.breakpoints.remove(&addr) {
+            self.breakpoints.insert(addr);
+        }
+    }
+
+    /// Where instructions start, found by decoding one after another from
+    /// address 0 and from the pointer, treating undecodable words as data.
+    fn instruction_starts(&self) -> Vec<usize> {
+        let memory = self.computer.memory();
+        let mut starts = BTreeSet::new();
+        for from in [0, self.computer.pointer].iter() {
+            let mut addr = *from;
+            while addr < memory.len() && starts.insert(addr) {
+                addr += disasm::decode(memory, addr).map_or(1, |instr| instr.length());
+            }
+        }
+        starts.into_iter().collect()
+    }
+
+    /// Steps once. Returns false if the program cannot go on.
+    fn step_once(&mut self) -> bool {
+        match self.computer.step() {
+            Some(Output::OutputVal(val)) => {
+                self.outputs.push(val);
+                true
+            }
+            Some(Output::WaitingForInput) => {
+                self.status = "Waiting for input, press i to give some".to_string();
+                false
+            }
+            Some(Output::Exit) => {
+                self.status = "Halted".to_string();
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn run(&mut self) {
+        for executed in 0..RUN_LIMIT {
+            if executed > 0 && !self.computer.waiting_for_input() && self.breakpoints.contains(&self.computer.pointer)
+            {
+                self.status = format!("Breakpoint at {}", self.computer.pointer);
+                return;
+            }
+            if !self.step_once() {
+                return;
+            }
+        }
+        self.status = format!("Stopped after {} steps", RUN_LIMIT);
+    }
+
+    /// Carries out a command. `Input` and `Quit` need the caller and do nothing here.
+    pub fn apply(&mut self, command: Command) {
+        match command {
+            Command::Step => {
+                if self.step_once() {
+                    self.status = format!("Stepped to {}", self.computer.pointer);
+                }
+                self.cursor = self.computer.pointer;
+            }
+            Command::Run => {
+                self.run();
+                self.cursor = self.computer.pointer;
+            }
+            Command::ToggleBreakpoint => self.toggle_breakpoint(self.cursor),
+            Command::CursorUp | Command::CursorDown => {
+                let starts = self.instruction_starts();
+                let index = starts.iter().position(|addr| *addr >= self.cursor).unwrap_or(0);
+                let index = if command == Command::CursorUp {
+                    index.saturating_sub(1)
+                } else {
+                    (index + 1).min(starts.len().saturating_sub(1))
+                };
+                self.cursor = starts.get(index).copied().unwrap_or(0);
+            }
+            Command::MemoryUp => self.memory_start = self.memory_start.saturating_sub(MEMORY_COLUMNS),
+            Command::MemoryDown => self.memory_start += MEMORY_COLUMNS,
+            Command::Input | Command::Quit => {}
+        }
+    }
+
+    fn disassembly_pane(&self, height: usize) -> Vec<String> {
+        let starts = self.instruction_starts();
+        let memory = self.computer.memory();
+        let pointer = self.computer.pointer;
+        let rows = height.saturating_sub(1);
+        let at = starts.iter().position(|addr| *addr == pointer).unwrap_or(0);
+        let first = at.saturating_sub(rows / 3).min(starts.len().saturating_sub(rows));
+
+        let mut pane = vec!["Disassembly".to_string()];
+        for addr in starts.iter().skip(first).take(rows) {
+            let text = disasm::decode(memory, *addr)
+                .map_or_else(|| format!("data {}", memory[*addr]), |instr| instr.to_string());
+            let address = if *addr == self.cursor {
+                format!("[{:>5}]", addr)
+            } else {
+                format!(" {:>5} ", addr)
+            };
+            pane.push(format!(
+                "{}{}{} {}",
+                if self.breakpoints.contains(addr) { '*' } else { ' ' },
+                if *addr == pointer { '>' } else { ' ' },
+                address,
+                text
+            ));
+        }
+        pane
+    }
+
+    fn memory_pane(&self, height: usize) -> Vec<String> {
+        let words = height.saturating_sub(2) * MEMORY_COLUMNS;
+        let range = self.memory_start..self.memory_start + words;
+        let mut pane = vec!["Memory".to_string()];
+        pane.extend(dump_memory(&self.computer.snapshot(), range, MEMORY_COLUMNS).lines().map(str::to_string));
+        pane
+    }
+
+    fn input_pane(&self, width: usize) -> Vec<String> {
+        let queued = self.computer.inputs.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+        let title = if self.computer.waiting_for_input() {
+            "Inputs (waiting)"
+        } else {
+            "Inputs"
+        };
+        vec![title.to_string(), fit(&queued.join(", "), width)]
+    }
+
+    fn output_pane(&self, height: usize) -> Vec<String> {
+        let shown = height.saturating_sub(1);
+        let mut pane = vec![format!("Outputs ({})", self.outputs.len())];
+        let recent = &self.outputs[self.outputs.len().saturating_sub(shown)..];
+        pane.extend(recent.iter().map(|v| v.to_string()));
+        pane
+    }
+
+    fn status_bar(&self) -> String {
+        let state = if self.computer.finished() {
+            "halted"
+        } else if self.computer.waiting_for_input() {
+            "waiting"
+        } else {
+            "paused"
+        };
+        format!(
+            "ptr {} rb {} steps {} {} | {} | s step r run b break j/k move [/] memory i input q quit",
+            self.computer.pointer,
+            self.computer.relative_base,
+            self.computer.instruction_count(),
+            state,
+            self.status
+        )
+    }
+
+    /// Renders the whole screen, `height` lines of `width` characters, with
+    /// disassembly on the left and memory, inputs and outputs on the right.
+    pub fn render(&self, width: usize, height: usize) -> String {
+        let body = height.saturating_sub(1);
+        let left_width = width / 2;
+        let right_width = width.saturating_sub(left_width + 3);
+
+        let memory_height = body.saturating_sub(2) / 2;
+        let mut right = self.memory_pane(memory_height);
+        right.resize(memory_height, String::new());
+        right.extend(self.input_pane(right_width));
+        right.extend(self.output_pane(body.saturating_sub(right.len())));
+
+        let left = self.disassembly_pane(body);
+        let mut screen = String::new();
+        for row in 0..body {
+            let left = left.get(row).map_or("", String::as_str);
+            let right = right.get(row).map_or("", String::as_str);
+            let line = format!("{} | {}", fit(left, left_width), fit(right, right_width));
+            screen += line.trim_end();
+            screen += "\n";
+        }
+        screen += fit(&self.status_bar(), width).trim_end();
+        screen += "\n";
+        screen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn countdown() -> Debugger {
+        let program = parse_program("3,100,1006,100,14,4,100,1001,100,-1,100,1105,1,2,99").unwrap();
+        Debugger::new(IntcodeComputer::new(&program))
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(Command::from_key(&Key::Char(' ')), Some(Command::Step));
+        assert_eq!(Command::from_key(&Key::ArrowDown), Some(Command::CursorDown));
+        assert_eq!(Command::from_key(&Key::Char('z')), None);
+    }
+
+    #[test]
+    fn run_to_breakpoint() {
+        let mut debugger = countdown();
+        debugger.apply(Command::Run);
+        assert_eq!(debugger.status, "Waiting for input, press i to give some");
+
+        // The pointer is already past the input instruction while it waits
+        debugger.queue_input(2);
+        debugger.apply(Command::CursorDown);
+        debugger.apply(Command::ToggleBreakpoint);
+        debugger.apply(Command::Run);
+        assert_eq!(debugger.status, "Breakpoint at 5");
+        debugger.apply(Command::Run);
+        debugger.apply(Command::Run);
+        assert_eq!(debugger.outputs, vec![2, 1]);
+        debugger.apply(Command::Run);
+        assert!(debugger.computer().finished());
+
+        // Too small to show much, but still renders
+        assert_eq!(debugger.render(10, 2).lines().count(), 2);
+    }
+
+    #[test]
+    fn render_layout() {
+        let mut debugger = countdown();
+        debugger.queue_input(3);
+        debugger.toggle_breakpoint(5);
+        debugger.apply(Command::Run);
+        debugger.apply(Command::Step);
+        debugger.apply(Command::CursorUp);
+
+        assert_eq!(
+            debugger.render(72, 12),
+            concat!(
+                "Disassembly                          | Memory\n",
+                "       0  in [100]                   | addr    +0    +1    +2    +3\n",
+                "       2  jz [100], 14               |    0 |   3   100 |1006   100\n",
+                "* [    5] out [100]                  |    4    14 |   4   100 >1001\n",
+                " >     7  add [100], -1, [100]       | Inputs\n",
+                "      11  jnz 1, 2                   |\n",
+                "      14  halt                       | Outputs (1)\n",
+                "      15  data 0                     | 3\n",
+                "      16  data 0                     |\n",
+                "      17  data 0                     |\n",
+                "      18  data 0                     |\n",
+                "ptr 7 rb 0 steps 3 paused | Stepped to 7 | s step r run b break j/k move\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Runs every intcode program in the repo with known inputs and compares the
+//! full results against the golden files in `tests/golden`. To regenerate the
+//! golden files after an intentional behaviour change, run:
+//!
+//!     INTCODE_BLESS=1 cargo test --test conformance
+//!
+//! Every program is run under both engines, which must agree.
+
+use intcode::{parse_program, Engine, IntcodeComputer, Output};
+use std::fs;
+use std::path::PathBuf;
+
+const DAY_5: &str = include_str!("../../day_5/input/day_5.txt");
+const DAY_7: &str = include_str!("../../day_7/input/day_7.txt");
+const DAY_9: &str = include_str!("../../day_9/input/day_9.txt");
+const DAY_11: &str = include_str!("../../day_11/input/day_11.txt");
+const DAY_13: &str = include_str!("../../day_13/input/day_13.txt");
+
+enum Inputs {
+    /// Given in order. The run stops if the program asks for more.
+    Fixed(Vec<i64>),
+    /// The same value, every time the program asks.
+    Constant(i64),
+}
+
+/// FNV-1a over the memory contents, ignoring trailing zeros so the hash does
+/// not depend on how far the interpreter happened to grow memory.
+fn memory_hash(memory: &[i64]) -> u64 {
+    let used = memory.iter().rposition(|v| *v != 0).map_or(0, |i| i + 1);
+    memory[..used]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+fn record(program: &str, patches: &[(usize, i64)], inputs: Inputs) -> String {
+    let mut memory = parse_program(program).unwrap();
+    for (addr, val) in patches {
+        memory[*addr] = *val;
+    }
+
+    let interpreted = record_with(Engine::Interpreter, &memory, &inputs);
+    let compiled = record_with(Engine::Compiled, &memory, &inputs);
+    assert_eq!(compiled, interpreted, "the compiled engine disagrees with the interpreter");
+    interpreted
+}
+
+fn record_with(engine: Engine, memory: &[i64], inputs: &Inputs) -> String {
+    let mut computer = IntcodeComputer::new(memory);
+    computer.set_engine(engine);
+    let mut fixed_inputs = match inputs {
+        Inputs::Fixed(values) => values.clone().into_iter(),
+        Inputs::Constant(_) => vec![].into_iter(),
+    };
+    let mut outputs = vec![];
+
+    let stop = loop {
+        match computer.tick() {
+            Output::OutputVal(x) => outputs.push(x),
+            Output::WaitingForInput => match *inputs {
+                Inputs::Fixed(_) => match fixed_inputs.next() {
+                    Some(input) => computer.provide_input(input),
+                    None => break "waiting for input",
+                },
+                Inputs::Constant(input) => computer.provide_input(input),
+            },
+            Output::Exit => break "halted",
+        }
+    };
+
+    format!(
+        "stop: {}\ninstructions: {}\nmemory: {:016x}\noutputs: {}\n",
+        stop,
+        computer.instruction_count(),
+        memory_hash(computer.memory()),
+        outputs
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+fn check(name: &str, actual: String) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.txt", name));
+
+    if std::env::var_os("INTCODE_BLESS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "Missing golden file {}, run with INTCODE_BLESS=1 to create it",
+            path.display()
+        )
+    });
+    for (expected_line, actual_line) in expected.lines().zip(actual.lines()) {
+        assert_eq!(actual_line, expected_line, "{} diverged from its golden file", name);
+    }
+    assert_eq!(actual, expected, "{} diverged from its golden file", name);
+}
+
+#[test]
+fn day_5_diagnostics() {
+    check("day_5_system_1", record(DAY_5, &[], Inputs::Fixed(vec![1])));
+    check("day_5_system_5", record(DAY_5, &[], Inputs::Fixed(vec![5])));
+}
+
+#[test]
+fn day_7_amplifiers() {
+    check("day_7_phase_0", record(DAY_7, &[], Inputs::Fixed(vec![0, 0])));
+    check("day_7_phase_4", record(DAY_7, &[], Inputs::Fixed(vec![4, 17])));
+    check("day_7_feedback_phase_9", record(DAY_7, &[], Inputs::Fixed(vec![9, 0, 3, 8])));
+}
+
+#[test]
+fn day_9_boost() {
+    check("day_9_test_mode", record(DAY_9, &[], Inputs::Fixed(vec![1])));
+    check("day_9_sensor_boost", record(DAY_9, &[], Inputs::Fixed(vec![2])));
+}
+
+#[test]
+fn day_11_paint_robot() {
+    check("day_11_all_black", record(DAY_11, &[], Inputs::Constant(0)));
+    check("day_11_all_white", record(DAY_11, &[], Inputs::Constant(1)));
+}
+
+#[test]
+fn day_13_arcade() {
+    check("day_13_attract_mode", record(DAY_13, &[], Inputs::Fixed(vec![])));
+    check("day_13_free_play_idle", record(DAY_13, &[(0, 2)], Inputs::Constant(0)));
+}
@@ -0,0 +1,65 @@
+//! Drives the DAP server through a recorded session, comparing every response
+//! and event with the expected transcript in `tests/dap`. To regenerate the
+//! transcript after an intentional behaviour change, run:
+//!
+//!     INTCODE_BLESS=1 cargo test --test dap
+
+use intcode::dap::{read_message, serve, write_message};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+fn dap_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("dap")
+}
+
+/// Replays `<name>.requests.jsonl` and compares what the server sends back
+/// with `<name>.expected.jsonl`, one message per line.
+fn check_session(name: &str) {
+    let dir = dap_dir();
+    let requests = fs::read_to_string(dir.join(format!("{}.requests.jsonl", name))).unwrap();
+    let requests = requests.replace("${dir}", &dir.display().to_string());
+
+    let mut input = vec![];
+    for line in requests.lines().filter(|line| !line.trim().is_empty()) {
+        let request: Value = serde_json::from_str(line).unwrap();
+        write_message(&mut input, &request).unwrap();
+    }
+
+    let mut output = vec![];
+    serve(&input[..], &mut output).unwrap();
+
+    let mut reader = &output[..];
+    let mut actual = String::new();
+    while let Some(message) = read_message(&mut reader).unwrap() {
+        actual += &format!("{}\n", message);
+    }
+    let actual = actual.replace(&dir.display().to_string(), "${dir}");
+
+    let path = dir.join(format!("{}.expected.jsonl", name));
+    if std::env::var_os("INTCODE_BLESS").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "Missing transcript {}, run with INTCODE_BLESS=1 to create it",
+            path.display()
+        )
+    });
+    for (expected_line, actual_line) in expected.lines().zip(actual.lines()) {
+        assert_eq!(actual_line, expected_line, "{} diverged from its transcript", name);
+    }
+    assert_eq!(actual, expected, "{} diverged from its transcript", name);
+}
+
+#[test]
+fn countdown_session() {
+    check_session("session");
+}
+
+#[test]
+fn run_to_exit_session() {
+    check_session("run_to_exit");
+}
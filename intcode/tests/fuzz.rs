@@ -0,0 +1,289 @@
+//! Differential fuzzing of `IntcodeComputer` against the reference interpreter.
+//!
+//! Random programs, both well formed and malformed, are run on both interpreters,
+//! with `IntcodeComputer` under each of its engines, within an instruction
+//! budget, comparing outputs, how the run stopped and the final memory. A
+//! divergence is minimised to a small reproducer, which is printed and saved
+//! under the target directory. Copy it into `tests/fuzz_regressions` by hand
+//! to have `saved_regressions` keep checking it.
+//!
+//! `INTCODE_FUZZ_CASES` and `INTCODE_FUZZ_SEED` change how many programs are
+//! generated and where the seeds start.
+
+mod reference;
+
+use intcode::{Engine, IntcodeComputer, Output};
+use reference::{Run, Stop};
+use std::cell::Cell;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Once;
+
+const BUDGET: u64 = 2_000;
+const MEMORY_LIMIT: usize = 4_096;
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Faults in `IntcodeComputer` are panics. Keep the expected ones off stderr
+/// without hiding the panics of a failing test.
+fn install_quiet_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(|quiet| quiet.get()) {
+                default_hook(info);
+            }
+        }));
+    });
+}
+
+fn run_computer(engine: Engine, program: &[i64], inputs: &[i64]) -> Run {
+    let mut computer = IntcodeComputer::new(program);
+    computer.set_engine(engine);
+    computer.set_memory_limit(MEMORY_LIMIT);
+    for input in inputs {
+        computer.queue_input(*input);
+    }
+
+    let mut outputs = vec![];
+    QUIET.with(|quiet| quiet.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| loop {
+        if computer.instruction_count() == BUDGET {
+            return Stop::OutOfBudget;
+        }
+        match computer.step() {
+            Some(Output::OutputVal(x)) => outputs.push(x),
+            Some(Output::WaitingForInput) => return Stop::WaitingForInput,
+            Some(Output::Exit) => return Stop::Halted,
+            None => {}
+        }
+    }));
+    QUIET.with(|quiet| quiet.set(false));
+
+    Run {
+        outputs,
+        stop: result.unwrap_or(Stop::Fault),
+        memory: computer.memory().to_vec(),
+    }
+}
+
+fn trimmed(memory: &[i64]) -> &[i64] {
+    &memory[..memory.iter().rposition(|v| *v != 0).map_or(0, |i| i + 1)]
+}
+
+/// Describes how the two interpreters disagree, if they do.
+fn divergence(program: &[i64], inputs: &[i64]) -> Option<String> {
+    let expected = reference::run(program, inputs, BUDGET, MEMORY_LIMIT);
+    [Engine::Interpreter, Engine::Compiled].iter().find_map(|engine| {
+        let actual = run_computer(*engine, program, inputs);
+        let reason = if actual.stop != expected.stop {
+            format!("stopped with {:?}, reference {:?}", actual.stop, expected.stop)
+        } else if actual.outputs != expected.outputs {
+            format!("output {:?}, reference {:?}", actual.outputs, expected.outputs)
+        } else if actual.stop != Stop::Fault && trimmed(&actual.memory) != trimmed(&expected.memory) {
+            format!(
+                "final memory {:?}, reference {:?}",
+                trimmed(&actual.memory),
+                trimmed(&expected.memory)
+            )
+        } else {
+            return None;
+        };
+        Some(format!("{:?} engine {}", engine, reason))
+    })
+}
+
+/// Shrinks a diverging program by dropping words and moving values towards
+/// zero for as long as it keeps diverging.
+fn minimise(mut program: Vec<i64>, inputs: &[i64]) -> Vec<i64> {
+    loop {
+        let mut shrunk = false;
+
+        for i in (0..program.len()).rev() {
+            let mut candidate = program.clone();
+            candidate.remove(i);
+            if !candidate.is_empty() && divergence(&candidate, inputs).is_some() {
+                program = candidate;
+                shrunk = true;
+            }
+        }
+
+        for i in 0..program.len() {
+            for smaller in [0, 1, program[i] / 10, program[i] / 2] {
+                if i64::abs(smaller) >= i64::abs(program[i]) {
+                    continue;
+                }
+                let mut candidate = program.clone();
+                candidate[i] = smaller;
+                if divergence(&candidate, inputs).is_some() {
+                    program = candidate;
+                    shrunk = true;
+                    break;
+                }
+            }
+        }
+
+        if !shrunk {
+            return program;
+        }
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn parse(values: &str) -> Vec<i64> {
+    values
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().unwrap())
+        .collect()
+}
+
+fn regressions_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fuzz_regressions")
+}
+
+/// Writes a reproducer to the target directory, so fuzzing never changes the
+/// source tree.
+fn save_regression(contents: &str) -> PathBuf {
+    let name = contents
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("fuzz_regressions");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{:016x}.txt", name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// xorshift64*, so every seed always generates the same program.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+}
+
+/// A program made of valid instructions with plausible operands, followed by
+/// some data.
+fn well_formed(rng: &mut Rng) -> Vec<i64> {
+    let mut program = vec![];
+    for _ in 0..rng.range(1, 24) {
+        let opcode = rng.range(1, 9);
+        let (reads, writes) = match opcode {
+            1 | 2 | 7 | 8 => (2, 1),
+            3 => (0, 1),
+            4 | 9 => (1, 0),
+            _ => (2, 0),
+        };
+
+        let mut word = opcode;
+        let mut operands = vec![];
+        for param in 0..reads + writes {
+            let mode = if param < reads { rng.range(0, 2) } else { [0, 2][rng.range(0, 1) as usize] };
+            word += mode * 10_i64.pow(param + 2);
+            operands.push(match mode {
+                0 => rng.range(0, 96),
+                1 => rng.range(-10, 96),
+                _ => rng.range(-4, 16),
+            });
+        }
+
+        program.push(word);
+        program.extend(operands);
+    }
+
+    program.push(99);
+    for _ in 0..rng.range(0, 8) {
+        program.push(rng.range(-5, 20));
+    }
+    program
+}
+
+/// Arbitrary words, biased towards things that look like instructions.
+fn malformed(rng: &mut Rng) -> Vec<i64> {
+    (0..rng.range(1, 40))
+        .map(|_| match rng.range(0, 5) {
+            0 => rng.range(0, 99) + 100 * rng.range(0, 9999),
+            1 => rng.range(1, 9) + 100 * rng.range(0, 222),
+            2 => 99,
+            _ => rng.range(-20, 80),
+        })
+        .collect()
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+#[test]
+fn differential_fuzz() {
+    install_quiet_panic_hook();
+    let first_seed = env_or("INTCODE_FUZZ_SEED", 0);
+    let cases = env_or("INTCODE_FUZZ_CASES", 3_000);
+
+    let mut failures = vec![];
+    for seed in first_seed..first_seed + cases {
+        let mut rng = Rng::new(seed);
+        let program = if seed % 2 == 0 {
+            well_formed(&mut rng)
+        } else {
+            malformed(&mut rng)
+        };
+        let inputs = (0..rng.range(0, 4)).map(|_| rng.range(-5, 10)).collect::<Vec<i64>>();
+
+        if divergence(&program, &inputs).is_some() {
+            let program = minimise(program, &inputs);
+            let reason = divergence(&program, &inputs).unwrap();
+            let reproducer = format!("program: {}\ninputs: {}\n", join(&program), join(&inputs));
+            let path = save_regression(&reproducer);
+            failures.push(format!("seed {}: {} (saved to {})\n{}", seed, reason, path.display(), reproducer));
+        }
+    }
+
+    assert!(failures.is_empty(), "Interpreters diverged:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn saved_regressions() {
+    install_quiet_panic_hook();
+    for entry in fs::read_dir(regressions_dir()).unwrap() {
+        let path = entry.unwrap().path();
+        let contents = fs::read_to_string(&path).unwrap();
+        let field = |name: &str| {
+            contents
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(parse)
+                .unwrap_or_default()
+        };
+
+        let (program, inputs) = (field("program: "), field("inputs: "));
+        if let Some(reason) = divergence(&program, &inputs) {
+            panic!("{} diverged: {}", path.display(), reason);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! A deliberately simple intcode interpreter, written for clarity rather than
+//! speed, to check `IntcodeComputer` against.
+//!
+//! Semantics, including the corners the puzzle text leaves open:
+//! - The opcode is the word modulo 100; the hundreds, thousands and ten
+//!   thousands digits are the modes of the first, second and third parameters.
+//!   Digits beyond the instruction's own parameters are ignored.
+//! - Every parameter is fetched, even a jump target that ends up unused, and
+//!   an address is checked as soon as it is worked out, even if the
+//!   instruction then waits for input.
+//! - A write parameter in immediate mode writes to the parameter's own address.
+//! - Memory outside the program reads as 0 and grows on demand, up to a limit.
+//! - Anything else is a fault: a zero, negative or unknown opcode, an unknown
+//!   mode, a negative address, an address past the limit, or arithmetic overflow.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Halted,
+    WaitingForInput,
+    OutOfBudget,
+    Fault,
+}
+
+pub struct Run {
+    pub outputs: Vec<i64>,
+    pub stop: Stop,
+    pub memory: Vec<i64>,
+}
+
+struct Machine {
+    memory: Vec<i64>,
+    memory_limit: usize,
+    pointer: i64,
+    relative_base: i64,
+}
+
+impl Machine {
+    fn load(&mut self, addr: i64) -> Result<i64, Stop> {
+        if addr < 0 || addr as usize >= self.memory_limit {
+            return Err(Stop::Fault);
+        }
+        Ok(self.memory.get(addr as usize).copied().unwrap_or(0))
+    }
+
+    fn store(&mut self, addr: i64, val: i64) -> Result<(), Stop> {
+        if addr < 0 || addr as usize >= self.memory_limit {
+            return Err(Stop::Fault);
+        }
+        let addr = addr as usize;
+        if addr >= self.memory.len() {
+            self.memory.resize(addr + 1, 0);
+        }
+        self.memory[addr] = val;
+        Ok(())
+    }
+
+    /// The address parameter `n` (counting from 1) of the current instruction refers to.
+    fn param_address(&mut self, word: i64, n: u32) -> Result<i64, Stop> {
+        let operand_addr = self.pointer + i64::from(n);
+        let mode = word / 10_i64.pow(n + 1) % 10;
+        let addr = match mode {
+            0 => self.load(operand_addr)?,
+            1 => operand_addr,
+            2 => self.relative_base + self.load(operand_addr)?,
+            _ => return Err(Stop::Fault),
+        };
+
+        if addr < 0 {
+            return Err(Stop::Fault);
+        }
+        Ok(addr)
+    }
+
+    fn param(&mut self, word: i64, n: u32) -> Result<i64, Stop> {
+        let addr = self.param_address(word, n)?;
+        self.load(addr)
+    }
+
+    /// Executes one instruction. Returns an output if it produced one.
+    fn step(&mut self, inputs: &mut Vec<i64>) -> Result<Option<i64>, Stop> {
+        let word = self.load(self.pointer)?;
+        if word <= 0 {
+            return Err(Stop::Fault);
+        }
+
+        match word % 100 {
+            1 | 2 | 7 | 8 => {
+                let a = self.param(word, 1)?;
+                let b = self.param(word, 2)?;
+                let dest = self.param_address(word, 3)?;
+                let result = match word % 100 {
+                    1 => a.checked_add(b).ok_or(Stop::Fault)?,
+                    2 => a.checked_mul(b).ok_or(Stop::Fault)?,
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+                self.store(dest, result)?;
+                self.pointer += 4;
+            }
+            3 => {
+                let dest = self.param_address(word, 1)?;
+                if inputs.is_empty() {
+                    return Err(Stop::WaitingForInput);
+                }
+                let input = inputs.remove(0);
+                self.store(dest, input)?;
+                self.pointer += 2;
+            }
+            4 => {
+                let value = self.param(word, 1)?;
+                self.pointer += 2;
+                return Ok(Some(value));
+            }
+            5 | 6 => {
+                let condition = self.param(word, 1)?;
+                let target = self.param(word, 2)?;
+                let jump = if word % 100 == 5 {
+                    condition != 0
+                } else {
+                    condition == 0
+                };
+                self.pointer = if jump { target } else { self.pointer + 3 };
+            }
+            9 => {
+                let offset = self.param(word, 1)?;
+                self.relative_base += offset;
+                self.pointer += 2;
+            }
+            99 => return Err(Stop::Halted),
+            _ => return Err(Stop::Fault),
+        }
+
+        Ok(None)
+    }
+}
+
+/// Runs `program` for at most `budget` instructions.
+pub fn run(program: &[i64], inputs: &[i64], budget: u64, memory_limit: usize) -> Run {
+    let mut machine = Machine {
+        memory: program.to_vec(),
+        memory_limit,
+        pointer: 0,
+        relative_base: 0,
+    };
+    let mut inputs = inputs.to_vec();
+    let mut outputs = vec![];
+
+    let mut executed = 0;
+    let stop = loop {
+        if executed == budget {
+            break Stop::OutOfBudget;
+        }
+        executed += 1;
+
+        match machine.step(&mut inputs) {
+            Ok(Some(output)) => outputs.push(output),
+            Ok(None) => {}
+            Err(stop) => break stop,
+        }
+    };
+
+    Run {
+        outputs,
+        stop,
+        memory: machine.memory,
+    }
+}